@@ -33,6 +33,15 @@ function js_str_to_rust(string) {
     buffer[string.length] = 0;
     return pointer;
 }
+function js_str_to_rust_buffer(string, max_length, length_ptr, pointer) {
+    const buffer = rust_ptr_to_buffer(pointer);
+    const length = Math.min(string.length, max_length - 1);
+    for(let i = 0; i < length; i++) {
+        buffer[i] = string.charCodeAt(i);
+    }
+    buffer[length] = 0;
+    rust_ptr_to_int32(length_ptr)[0] = length;
+}
 //Generate the keycodes from an in-order list that maps to the Glutin keycodes in rust
 const keynames = ["Digit1", "Digit2", "Digit3", "Digit4", "Digit5", "Digit6", "Digit7", "Digit8", "Digit9", "Digit0", "KeyA", "KeyB", "KeyC", "KeyD", "KeyE", "KeyF", "KeyG", "KeyH", "KeyI", "KeyJ", "KeyK", "KeyL", "KeyM", 
     "KeyN", "KeyO", "KeyP", "KeyQ", "KeyR", "KeyS", "KeyT", "KeyU", "KeyV", "KeyW", "KeyX", "KeyY", "KeyZ", "Escape", "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12", 
@@ -261,16 +270,17 @@ let env = {
     ffi_asset_status: (index) => assets[index].error ? 2 : (assets[index].loaded ? 1 : 0),
     //Game loop
     set_app: (app) => {
-        setInterval(() => instance.exports.update(app), 16);
         send_event = (event) => {
             instance.exports.event(app, event);
         };
-        function draw() {
-            instance.exports.draw(app);
-            requestAnimationFrame(draw);
+        function frame() {
+            instance.exports.frame(app);
+            requestAnimationFrame(frame);
         }
-        requestAnimationFrame(draw);
+        requestAnimationFrame(frame);
     },
+    current_time: () => performance.now(),
+    log_error: (message) => console.error(rust_str_to_js(message)),
     //Rust runtime
     fmodf: (a, b) => a % b,
     sinf: (x) => Math.sin(x),
@@ -290,8 +300,10 @@ let env = {
     CompileShader: (index) => gl.compileShader(gl_objects[index]),
     CreateShader: (type) => gl_objects.push(gl.createShader(type)) - 1,
     CreateProgram: () => gl_objects.push(gl.createProgram()) - 1,
+    BindAttribLocation: (index, location, string_ptr) => gl.bindAttribLocation(gl_objects[index], location, rust_str_to_js(string_ptr)),
     BindBuffer: (mask, index) => gl.bindBuffer(mask, gl_objects[index]),
     BindFramebuffer: (target, index) => gl.bindFramebuffer(target, index == 0 ? null : gl_objects[index]),
+    BindRenderbuffer: (target, index) => gl.bindRenderbuffer(target, gl_objects[index]),
     BindTexture: (target, index) => gl.bindTexture(target, gl_objects[index]),
     BindVertexArray: (index) => gl.bindVertexArray(gl_objects[index]),
     BlendEquationSeparate: gl.blendEquationSeparate.bind(gl),
@@ -301,6 +313,7 @@ let env = {
     DeleteBuffer: (index) => gl.deleteBuffer(gl_objects[index]),
     DeleteFramebuffer: (index) => gl.deleteFramebuffer(gl_objects[index]),
     DeleteProgram: (index) => gl.deleteProgram(gl_objects[index]),
+    DeleteRenderbuffer: (index) => gl.deleteRenderbuffer(gl_objects[index]),
     DeleteShader: (index) => gl.deleteShader(gl_objects[index]),
     DeleteTexture: (index) => gl.deleteTexture(gl_objects[index]),
     DeleteVertexArray: (index) => gl.deleteVertexArray(gl_objects[index]),
@@ -308,16 +321,24 @@ let env = {
     DrawElements: gl.drawElements.bind(gl), 
     Enable: gl.enable.bind(gl),
     EnableVertexAttribArray: gl.enableVertexAttribArray.bind(gl),
+    FramebufferRenderbuffer: (target, attachment, rbtarget, index) => gl.framebufferRenderbuffer(target, attachment, rbtarget, gl_objects[index]),
     FramebufferTexture: (target, attachment, tex_index, level) => gl.framebufferTexture2D(target, attachment, gl.TEXTURE_2D, gl_objects[tex_index], level),
     GenBuffer: () => gl_objects.push(gl.createBuffer()) - 1,
     GenerateMipmap: gl.generateMipmap.bind(gl),
     GenFramebuffer: () => gl_objects.push(gl.createFramebuffer()) - 1,
+    GenRenderbuffer: () => gl_objects.push(gl.createRenderbuffer()) - 1,
     GenTexture: () => gl_objects.push(gl.createTexture()) - 1,
     GenVertexArray: () => gl_objects.push(gl.createVertexArray()) - 1,
     GetAttribLocation: (index, string_ptr) => gl.getAttribLocation(gl_objects[index], rust_str_to_js(string_ptr)),    
-    GetShaderInfoLog: (index) => { let str = gl.getShaderInfoLog(gl_objects[index]); console.log(str); return str; }, //todo: convert to rust string?
-    GetShaderiv: (index, param) => gl.getShaderParameter(gl_objects[index], param),
-    GetUniformLocation: (index, string_ptr) => gl_objects.push(gl.getUniformLocation(gl_objects[index], rust_str_to_js(string_ptr))) - 1,
+    GetProgramInfoLog: (index, max_length, length_ptr, log_ptr) => js_str_to_rust_buffer(gl.getProgramInfoLog(gl_objects[index]), max_length, length_ptr, log_ptr),
+    GetIntegerv: (param, ptr) => rust_ptr_to_int32(ptr)[0] = gl.getParameter(param),
+    GetProgramiv: (index, param, ptr) => rust_ptr_to_int32(ptr)[0] = gl.getProgramParameter(gl_objects[index], param),
+    GetShaderInfoLog: (index, max_length, length_ptr, log_ptr) => js_str_to_rust_buffer(gl.getShaderInfoLog(gl_objects[index]), max_length, length_ptr, log_ptr),
+    GetShaderiv: (index, param, ptr) => rust_ptr_to_int32(ptr)[0] = gl.getShaderParameter(gl_objects[index], param),
+    GetUniformLocation: (index, string_ptr) => {
+        const location = gl.getUniformLocation(gl_objects[index], rust_str_to_js(string_ptr));
+        return location === null ? -1 : gl_objects.push(location) - 1;
+    },
     GetViewport: (ptr) => {
         const buffer = rust_ptr_to_int32(ptr);
         const values = gl.getParameter(gl.VIEWPORT);
@@ -326,12 +347,20 @@ let env = {
         }
     },
     LinkProgram: (index) => gl.linkProgram(gl_objects[index]),
+    ReadPixels: (x, y, width, height, format, pixel_type, data) => 
+        gl.readPixels(x, y, width, height, format, pixel_type, new Uint8Array(instance.exports.memory.buffer, data, width * height * 4)),
+    RenderbufferStorage: gl.renderbufferStorage.bind(gl),
     ShaderSource: (shader, source_ptr) => gl.shaderSource(gl_objects[shader], rust_str_to_js(source_ptr)),
     TexImage2D: (target, level, internal, width, height, border, format, textype, data) => 
         gl_objects.push(gl.texImage2D(target, level, internal, width, height, border, format, textype, 
                                       data == 0 ? new Uint8Array(width * height * 4) : rust_ptr_to_buffer(data), 0)) - 1,
-    TexParameteri: (target, pname, param) => gl.texParameteri.bind(gl),
+    TexParameteri: gl.texParameteri.bind(gl),
+    Uniform1f: (index, value) => gl.uniform1f(gl_objects[index], value),
     Uniform1i: (index, value) => gl.uniform1i(gl_objects[index], value),
+    Uniform2f: (index, x, y) => gl.uniform2f(gl_objects[index], x, y),
+    Uniform4f: (index, x, y, z, w) => gl.uniform4f(gl_objects[index], x, y, z, w),
+    UniformMatrix3fv: (index, count, transpose, ptr) => 
+        gl.uniformMatrix3fv(gl_objects[index], transpose != 0, new Float32Array(instance.exports.memory.buffer, ptr, 9 * count)),
     UseProgram: (index) => gl.useProgram(gl_objects[index]),
     VertexAttribPointer: gl.vertexAttribPointer.bind(gl),
     Viewport: gl.viewport.bind(gl),
@@ -2,25 +2,36 @@
 extern crate quicksilver;
 
 use quicksilver::{
-    State, run,
-    geom::{Circle, Rectangle, Transform},
-    graphics::{Color, Draw, Window, WindowBuilder}
+    Result, State, run,
+    geom::{Circle, Rectangle, Transform, Vector},
+    graphics::{Color, Draw, LineCap, LineJoin, Stroke, StrokeAlignment, Window, WindowBuilder}
 };
 
 struct DrawGeometry;
 
 impl State for DrawGeometry {
-    fn new() -> DrawGeometry { DrawGeometry }
+    fn new() -> Result<DrawGeometry> { Ok(DrawGeometry) }
 
-   fn draw(&mut self, window: &mut Window) {
+   fn draw(&mut self, window: &mut Window) -> Result<()> {
         window.clear(Color::black());
         window.draw(&Draw::rectangle(Rectangle::new(100, 100, 32, 32)).with_color(Color::red()));
         window.draw(&Draw::rectangle(Rectangle::new(400, 300, 32, 32)).with_color(Color::blue()).with_transform(Transform::rotate(45)).with_z(10));
         window.draw(&Draw::circle(Circle::new(400, 300, 100)).with_color(Color::green()));
+        window.draw(&Draw::line(Vector::new(50, 500), Vector::new(250, 550), Stroke::new(5).with_cap(LineCap::Round)).with_color(Color::yellow()));
+        window.draw(&Draw::polyline(&[Vector::new(550, 100), Vector::new(600, 50), Vector::new(650, 100), Vector::new(700, 50)],
+            Stroke::new(8).with_join(LineJoin::Round)).with_color(Color::white()));
+        window.draw(&Draw::triangle(Vector::new(600, 500), Vector::new(700, 500), Vector::new(650, 420)).with_color(Color::orange()));
+        window.draw(&Draw::polygon(&[Vector::new(100, 250), Vector::new(200, 250), Vector::new(150, 300), Vector::new(200, 350), Vector::new(100, 350)])
+            .with_color(Color::red()));
+        window.draw(&Draw::circle_outline(Circle::new(400, 300, 100), Stroke::new(4).with_alignment(StrokeAlignment::Outside)).with_color(Color::white()));
+        window.draw(&Draw::rectangle_outline(Rectangle::new(100, 100, 32, 32), Stroke::new(2).with_alignment(StrokeAlignment::Inside)).with_color(Color::white()).with_z(5));
+        window.draw(&Draw::sector(Circle::new(700, 300, 60), -30.0, 210.0).with_color(Color::yellow()));
+        window.draw(&Draw::rounded_rectangle(Rectangle::new(50, 420, 150, 50), 12.0).with_color(Color::blue()));
         window.present();
+        Ok(())
    }
 }
 
 fn main() {
-    run::<DrawGeometry>(WindowBuilder::new("Draw Geometry", 800, 600));
+    run::<DrawGeometry>(WindowBuilder::new("Draw Geometry", 800, 600)).unwrap();
 }
@@ -4,7 +4,7 @@ extern crate quicksilver;
 
 use futures::{Async, Future};
 use quicksilver::{
-    State, run,
+    Result, State, Transition, run,
     geom::Vector,
     graphics::{Color, Font, FontLoader, Image, Draw, Window, WindowBuilder}
 };
@@ -15,30 +15,32 @@ enum SampleText {
 }
 
 impl State for SampleText {
-    fn new() -> SampleText { SampleText::Loading(Font::load("examples/assets/font.ttf")) }
+    fn new() -> Result<SampleText> { Ok(SampleText::Loading(Font::load("examples/assets/font.ttf"))) }
 
-   fn update(&mut self, _: &mut Window) {
+   fn update(&mut self, _: &mut Window) -> Result<Transition> {
        // Check to see the progress of the loading font 
        let result = match self {
-           &mut SampleText::Loading(ref mut loader) => loader.poll().unwrap(),
+           &mut SampleText::Loading(ref mut loader) => loader.poll()?,
            _ => Async::NotReady
        };
        // If the image has been loaded move to the loaded state
        if let Async::Ready(font) = result {
            *self = SampleText::Loaded(font.render("Sample Text", 72.0, Color::black()));
        }
+       Ok(Transition::None)
    }
 
-   fn draw(&mut self, window: &mut Window) {
+   fn draw(&mut self, window: &mut Window) -> Result<()> {
         window.clear(Color::white());
         // If the image is loaded draw it
         if let &mut SampleText::Loaded(ref image) = self {
             window.draw(&Draw::image(image, Vector::new(400, 300)));
         }
         window.present();
+        Ok(())
    }
 }
 
 fn main() {
-    run::<SampleText>(WindowBuilder::new("Font Example", 800, 600));
+    run::<SampleText>(WindowBuilder::new("Font Example", 800, 600)).unwrap();
 }
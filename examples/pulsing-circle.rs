@@ -2,7 +2,7 @@
 extern crate quicksilver;
 
 use quicksilver::{
-    State, run,
+    Result, State, Transition, run,
     geom::{Circle, Vector, Transform},
     graphics::{Color, Draw, Window, WindowBuilder}
 };
@@ -12,22 +12,24 @@ struct PulsingCircle {
 }
 
 impl State for PulsingCircle {
-   fn new() -> PulsingCircle { 
-       PulsingCircle { step: 0.0 }
+   fn new() -> Result<PulsingCircle> { 
+       Ok(PulsingCircle { step: 0.0 })
    }
 
-   fn update(&mut self, _window: &mut Window) {
+   fn update(&mut self, _window: &mut Window) -> Result<Transition> {
        self.step = (self.step + 1.0) % 360.0;
+       Ok(Transition::None)
    }
 
-   fn draw(&mut self, window: &mut Window) {
+   fn draw(&mut self, window: &mut Window) -> Result<()> {
         window.clear(Color::black());
         let scale = Transform::scale(Vector::one() * (1.0 + (self.step.to_radians().sin() / 2.0)));
         window.draw(&Draw::circle(Circle::new(400, 300, 50)).with_color(Color::green()).with_transform(scale));
         window.present();
+        Ok(())
    }
 }
 
 fn main() {
-    run::<PulsingCircle>(WindowBuilder::new("Pulsing Circle", 800, 600));
+    run::<PulsingCircle>(WindowBuilder::new("Pulsing Circle", 800, 600)).unwrap();
 }
@@ -0,0 +1,256 @@
+// Pack a directory of sprites into page images and a libgdx atlas manifest for `Atlas::load`
+//
+// Usage: pack-atlas <sprite directory> <output path> [--size WIDTHxHEIGHT] [--padding N] [--extrude N]
+//
+// Writing to `assets/sprites` creates `assets/sprites.atlas` and `assets/sprites.png`, with more
+// pages called `sprites2.png` and so on. Sprites named like `walk_0.png` and `walk_1.png` are
+// the frames of an animation called `walk`, and other sprites are still images named by file.
+#![cfg_attr(target_arch="wasm32", allow(dead_code))]
+#[cfg(not(target_arch="wasm32"))]
+extern crate image;
+extern crate quicksilver;
+
+#[cfg(not(target_arch="wasm32"))]
+use image::RgbaImage;
+#[cfg(not(target_arch="wasm32"))]
+use quicksilver::graphics::pack_rectangles;
+#[cfg(not(target_arch="wasm32"))]
+use std::{
+    env,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    process
+};
+
+const USAGE: &str = "Usage: pack-atlas <sprite directory> <output path> [--size WIDTHxHEIGHT] [--padding N] [--extrude N]";
+
+struct Options {
+    input: String,
+    output: String,
+    page: (u32, u32),
+    padding: u32,
+    extrusion: u32
+}
+
+// A sprite's place in the atlas, as (name, index, page, x, y, width, height)
+type Entry = (String, i32, usize, u32, u32, u32, u32);
+
+#[cfg(not(target_arch="wasm32"))]
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = parse_args(&args).and_then(|options| pack(&options));
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+}
+
+#[cfg(target_arch="wasm32")]
+fn main() {}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    fn number(value: Option<&String>) -> Result<u32, String> {
+        value.and_then(|value| value.parse().ok()).ok_or_else(|| USAGE.to_owned())
+    }
+    let mut positional = Vec::new();
+    let mut options = Options {
+        input: String::new(),
+        output: String::new(),
+        page: (1024, 1024),
+        padding: 2,
+        extrusion: 0
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => {
+                let size = args.next().ok_or_else(|| USAGE.to_owned())?;
+                let mut dimensions = size.split('x').map(|value| value.parse::<u32>());
+                options.page = match (dimensions.next(), dimensions.next(), dimensions.next()) {
+                    (Some(Ok(width)), Some(Ok(height)), None) => (width, height),
+                    _ => return Err(USAGE.to_owned())
+                };
+            }
+            "--padding" => options.padding = number(args.next())?,
+            "--extrude" => options.extrusion = number(args.next())?,
+            _ => positional.push(arg.clone())
+        }
+    }
+    if positional.len() != 2 {
+        return Err(USAGE.to_owned());
+    }
+    options.output = positional.pop().unwrap();
+    options.input = positional.pop().unwrap();
+    Ok(options)
+}
+
+// Split a sprite's file name into its atlas name and its index within an animation
+fn frame_name(stem: &str) -> (String, i32) {
+    if let Some(split) = stem.rfind('_') {
+        if let Ok(index) = stem[split + 1..].parse::<u32>() {
+            if split > 0 {
+                return (stem[..split].to_owned(), index as i32);
+            }
+        }
+    }
+    (stem.to_owned(), -1)
+}
+
+// Find two sprites that would be merged into one atlas item, which happens when a still image
+// shares its name with the frames of an animation or two frames have the same index
+fn name_collision(names: &[(String, i32)]) -> Option<(usize, usize)> {
+    for (first, &(ref name, index)) in names.iter().enumerate() {
+        for (second, &(ref other_name, other_index)) in names.iter().enumerate().skip(first + 1) {
+            if name == other_name && (index == other_index || index < 0 || other_index < 0) {
+                return Some((first, second));
+            }
+        }
+    }
+    None
+}
+
+// Write the libgdx manifest for the pages and the sprites on them
+fn manifest(pages: &[String], page: (u32, u32), entries: &[Entry]) -> String {
+    let mut manifest = String::new();
+    for (page_index, page_name) in pages.iter().enumerate() {
+        if page_index > 0 {
+            manifest.push('\n');
+        }
+        manifest.push_str(&format!("{}\nsize: {},{}\nformat: RGBA8888\nfilter: Nearest,Nearest\nrepeat: none\n",
+                                   page_name, page.0, page.1));
+        for &(ref name, index, _, x, y, width, height) in entries.iter().filter(|entry| entry.2 == page_index) {
+            manifest.push_str(&format!("{}\n  rotate: false\n  xy: {}, {}\n  size: {}, {}\n  orig: {}, {}\n  offset: 0, 0\n  index: {}\n",
+                                       name, x, y, width, height, width, height, index));
+        }
+    }
+    manifest
+}
+
+#[cfg(not(target_arch="wasm32"))]
+fn pack(options: &Options) -> Result<(), String> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(&options.input)
+        .map_err(|err| format!("Failed to read {}: {}", options.input, err))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map(|extension| extension == "png").unwrap_or(false))
+        .collect();
+    paths.sort();
+    let names: Vec<(String, i32)> = paths.iter()
+        .map(|path| frame_name(&path.file_stem().unwrap().to_string_lossy()))
+        .collect();
+    if let Some((first, second)) = name_collision(&names) {
+        return Err(format!("{} and {} would both be {} in the atlas, so one of them needs renaming",
+                           paths[first].display(), paths[second].display(), names[first].0));
+    }
+    let mut sprites = Vec::with_capacity(paths.len());
+    for (path, (name, index)) in paths.iter().zip(names.into_iter()) {
+        let image = image::open(path).map_err(|err| format!("Failed to load {}: {}", path.display(), err))?.to_rgba();
+        sprites.push((name, index, image));
+    }
+    let border = 2 * options.extrusion + options.padding;
+    let sizes: Vec<(u32, u32)> = sprites.iter()
+        .map(|&(_, _, ref image)| (image.width() + border, image.height() + border))
+        .collect();
+    // Every cell is followed by the padding, which may hang off the edge of the page
+    let placements = pack_rectangles(&sizes, (options.page.0 + options.padding, options.page.1 + options.padding))
+        .map_err(|index| format!("{} is too large for a {}x{} page", paths[index].display(), options.page.0, options.page.1))?;
+    let page_count = placements.iter().map(|&(page, _, _)| page + 1).max().unwrap_or(0);
+    let output = Path::new(&options.output);
+    let stem = output.file_name().ok_or_else(|| USAGE.to_owned())?.to_string_lossy().into_owned();
+    let page_names: Vec<String> = (0..page_count)
+        .map(|page| if page == 0 { format!("{}.png", stem) } else { format!("{}{}.png", stem, page + 1) })
+        .collect();
+    let mut pages: Vec<RgbaImage> = (0..page_count).map(|_| RgbaImage::new(options.page.0, options.page.1)).collect();
+    let mut entries = Vec::with_capacity(sprites.len());
+    for (&(ref name, index, ref image), &(page, x, y)) in sprites.iter().zip(placements.iter()) {
+        let (x, y) = (x + options.extrusion, y + options.extrusion);
+        extrude(&mut pages[page], image, x, y, options.extrusion);
+        entries.push((name.clone(), index, page, x, y, image.width(), image.height()));
+    }
+    let directory = output.parent().unwrap_or(Path::new(""));
+    for (page, name) in pages.iter().zip(page_names.iter()) {
+        let path = directory.join(name);
+        page.save(&path).map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+    }
+    let path = directory.join(format!("{}.atlas", stem));
+    File::create(&path)
+        .and_then(|mut file| file.write_all(manifest(&page_names, options.page, &entries).as_bytes()))
+        .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+}
+
+// Copy a sprite onto a page, repeating its edge pixels around it
+#[cfg(not(target_arch="wasm32"))]
+fn extrude(page: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32, extrusion: u32) {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let extrusion = extrusion as i64;
+    for dy in -extrusion..height + extrusion {
+        for dx in -extrusion..width + extrusion {
+            let source = image.get_pixel(dx.max(0).min(width - 1) as u32, dy.max(0).min(height - 1) as u32);
+            page.put_pixel((x as i64 + dx) as u32, (y as i64 + dy) as u32, *source);
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch="wasm32")))]
+mod tests {
+    use super::*;
+    use quicksilver::{Headless, Result, State};
+    use quicksilver::geom::{Rectangle, Vector};
+    use quicksilver::graphics::{Atlas, AtlasItem, Image, PixelFormat, WindowBuilder};
+
+    #[test]
+    fn frame_names() {
+        assert_eq!(frame_name("walk_3"), ("walk".to_owned(), 3));
+        assert_eq!(frame_name("big_tree"), ("big_tree".to_owned(), -1));
+        assert_eq!(frame_name("_2"), ("_2".to_owned(), -1));
+    }
+
+    #[test]
+    fn libgdx_manifest() {
+        let entries = vec![("walk".to_owned(), 0, 0, 1, 1, 8, 16), ("coin".to_owned(), -1, 1, 1, 1, 4, 4)];
+        let manifest = manifest(&["sprites.png".to_owned(), "sprites2.png".to_owned()], (64, 64), &entries);
+        assert_eq!(manifest, "sprites.png\nsize: 64,64\nformat: RGBA8888\nfilter: Nearest,Nearest\nrepeat: none\n\
+            walk\n  rotate: false\n  xy: 1, 1\n  size: 8, 16\n  orig: 8, 16\n  offset: 0, 0\n  index: 0\n\
+            \nsprites2.png\nsize: 64,64\nformat: RGBA8888\nfilter: Nearest,Nearest\nrepeat: none\n\
+            coin\n  rotate: false\n  xy: 1, 1\n  size: 4, 4\n  orig: 4, 4\n  offset: 0, 0\n  index: -1\n");
+    }
+
+    #[test]
+    fn collisions() {
+        let names = |stems: &[&str]| stems.iter().map(|stem| frame_name(stem)).collect::<Vec<_>>();
+        assert_eq!(name_collision(&names(&["walk_0", "walk_1", "coin"])), None);
+        assert_eq!(name_collision(&names(&["walk", "walk_0", "walk_1"])), Some((0, 1)));
+        assert_eq!(name_collision(&names(&["coin", "walk_1", "walk_01"])), Some((1, 2)));
+    }
+
+    struct Blank;
+
+    impl State for Blank {
+        fn new() -> Result<Blank> {
+            Ok(Blank)
+        }
+    }
+
+    #[test]
+    fn atlas_reads_manifest() {
+        // Images made without a graphics context don't need one
+        let _game = Headless::new::<Blank>(WindowBuilder::new("", 64, 64)).unwrap();
+        let entries = vec![
+            ("walk".to_owned(), 1, 0, 11, 1, 8, 16),
+            ("walk".to_owned(), 0, 0, 1, 1, 8, 16),
+            ("coin".to_owned(), -1, 1, 1, 1, 4, 4)
+        ];
+        let pages = ["sprites.png".to_owned(), "sprites2.png".to_owned()];
+        let images = (0..2).map(|_| Image::from_raw(&[], 64, 64, PixelFormat::RGBA)).collect();
+        let atlas = Atlas::from_manifest(&manifest(&pages, (64, 64), &entries), images).unwrap();
+        let frames = match atlas.get("walk") {
+            Some(AtlasItem::Animation(frames)) => frames,
+            other => panic!("walk should be an animation, not {:?}", other)
+        };
+        let areas: Vec<Rectangle> = frames.iter().map(Image::area).collect();
+        assert_eq!(areas, vec![Rectangle::new(1, 1, 8, 16), Rectangle::new(11, 1, 8, 16)]);
+        let coin = atlas.get("coin").unwrap().unwrap_image();
+        assert_eq!(coin.area().size(), Vector::new(4, 4));
+        assert!(!coin.is_rotated());
+    }
+}
@@ -5,7 +5,7 @@ extern crate serde_json;
 #[cfg(all(not(target_arch="wasm32"), feature="rodio"))] 
 extern crate rodio;
 
-use graphics::{AtlasError, ImageError};
+use graphics::{AtlasError, ImageError, ShaderError};
 #[cfg(feature="rusttype")] use rusttype::Error as FontError;
 #[cfg(feature="serde_json")] use serde_json::Error as SerdeError;
 #[cfg(feature="sounds")] use sound::SoundError;
@@ -15,6 +15,9 @@ use std::{
     error::Error
 };
 
+/// A Result that returns either success or a Quicksilver Error
+pub type Result<T> = ::std::result::Result<T, QuicksilverError>;
+
 #[derive(Debug)]
 /// An error generated by some Quicksilver subsystem
 pub enum QuicksilverError {
@@ -22,6 +25,8 @@ pub enum QuicksilverError {
     AtlasError(AtlasError),
     /// An error from loading an image
     ImageError(ImageError),
+    /// An error from compiling or linking a shader
+    ShaderError(ShaderError),
     /// An error from loading a sound
     #[cfg(feature="sounds")] SoundError(SoundError),
     /// An error from loading a file
@@ -34,7 +39,11 @@ pub enum QuicksilverError {
 
 impl fmt::Display for QuicksilverError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.description())
+        match self {
+            // Shader errors carry the compiler's log, which is the useful part
+            &QuicksilverError::ShaderError(ref err) => write!(f, "{}", err),
+            _ => write!(f, "{}", self.description())
+        }
     }
 }
 
@@ -43,6 +52,7 @@ impl Error for QuicksilverError {
         match self {
             &QuicksilverError::AtlasError(ref err) => err.description(),
             &QuicksilverError::ImageError(ref err) => err.description(),
+            &QuicksilverError::ShaderError(ref err) => err.description(),
             &QuicksilverError::SoundError(ref err) => err.description(),
             &QuicksilverError::IOError(ref err) => err.description(),
             &QuicksilverError::SerdeError(ref err) => err.description(),
@@ -54,6 +64,7 @@ impl Error for QuicksilverError {
         match self {
             &QuicksilverError::AtlasError(ref err) => Some(err),
             &QuicksilverError::ImageError(ref err) => Some(err),
+            &QuicksilverError::ShaderError(ref err) => Some(err),
             &QuicksilverError::SoundError(ref err) => Some(err),
             &QuicksilverError::IOError(ref err) => Some(err),
             &QuicksilverError::SerdeError(ref err) => Some(err),
@@ -69,6 +80,13 @@ impl From<ImageError> for QuicksilverError {
     }
 }
 
+#[doc(hidden)]
+impl From<ShaderError> for QuicksilverError {
+    fn from(err: ShaderError) -> QuicksilverError {
+        QuicksilverError::ShaderError(err)
+    }
+}
+
 #[doc(hidden)]
 #[cfg(feature="sounds")]
 impl From<SoundError> for QuicksilverError {
@@ -4,6 +4,21 @@ extern crate gl;
 
 pub use self::gl::*;
 
+use std::cell::Cell;
+
+thread_local! {
+    static HEADLESS: Cell<bool> = Cell::new(false);
+}
+
+// Headless windows have no context, so nothing should call into OpenGL while one is in use
+pub fn set_headless(headless: bool) {
+    HEADLESS.with(|flag| flag.set(headless));
+}
+
+pub fn is_headless() -> bool {
+    HEADLESS.with(|flag| flag.get())
+}
+
 pub unsafe fn GetViewport(target: *mut i32) {
     gl::GetIntegerv(gl::VIEWPORT, target);
 }
@@ -20,6 +35,10 @@ pub unsafe fn DeleteFramebuffer(id: u32) {
     gl::DeleteFramebuffers(1, &id as *const u32);
 }
 
+pub unsafe fn DeleteRenderbuffer(id: u32) {
+    gl::DeleteRenderbuffers(1, &id as *const u32);
+}
+
 pub unsafe fn DeleteTexture(id: u32) {
     gl::DeleteTextures(1, &id as *const u32);
 }
@@ -40,6 +59,12 @@ pub unsafe fn GenFramebuffer() -> u32 {
     buffer
 }
 
+pub unsafe fn GenRenderbuffer() -> u32 {
+    let mut buffer = 0;
+    gl::GenRenderbuffers(1, &mut buffer as *mut u32);
+    buffer
+}
+
 pub unsafe fn GenTexture() -> u32 {
     let mut texture = 0;
     gl::GenTextures(1, &mut texture as *mut u32);
@@ -48,6 +48,8 @@ extern "C" {
     fn ffi_asset_status(handle: u32) -> i32;
     //Game loop
     pub fn set_app(app: *mut c_void);
+    pub fn current_time() -> f64;
+    pub fn log_error(message: *mut i8);
 }
 
 #[derive(Debug)]
@@ -33,7 +33,7 @@ use std::{
 /// transform * Vector::new(5, 5)
 /// # ;
 /// ```
-pub struct Transform([[f32; 3]; 3]);
+pub struct Transform(pub(crate) [[f32; 3]; 3]);
 
 impl Transform {
     ///Create an identity transformation
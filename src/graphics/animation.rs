@@ -4,11 +4,12 @@ use std::rc::Rc;
 
 #[derive(Debug)]
 struct AnimationData {
-    frames: Vec<Image>
+    frames: Vec<Image>,
+    delays: Vec<u32>
 }
 
 #[derive(Clone, Debug)]
-/// A linear series of images with a constant frame delay
+/// A linear series of images, each shown for a number of ticks
 ///
 /// Frames advance by discrete ticks, which should be run in the `update` section of a 
 /// quicksilver application loop rather than the `draw` section. Draws may happen as 
@@ -16,20 +17,24 @@ struct AnimationData {
 pub struct Animation {
     data: Rc<AnimationData>,
     current: usize,
-    current_time: u32,
-    frame_delay: u32
+    current_time: u32
 }
 
 impl Animation {
     /// Create a new animation from a series of images and a frame delay
     pub fn new<I>(images: I, frame_delay: u32) -> Animation 
         where I: IntoIterator<Item = Image> {
-        let frames = images.into_iter().collect();
+        Animation::from_frames(images.into_iter().map(|image| (image, frame_delay)))
+    }
+
+    /// Create a new animation from a series of images, each with its own delay in ticks
+    pub fn from_frames<I>(frames: I) -> Animation
+        where I: IntoIterator<Item = (Image, u32)> {
+        let (frames, delays) = frames.into_iter().unzip();
         Animation {
-            data: Rc::new(AnimationData { frames }),
+            data: Rc::new(AnimationData { frames, delays }),
             current: 0,
-            current_time: 0,
-            frame_delay
+            current_time: 0
         }
     }
 
@@ -43,7 +48,7 @@ impl Animation {
     /// Tick the animation forward by one step
     pub fn tick(&mut self) {
         self.current_time += 1;
-        if self.current_time >= self.frame_delay {
+        if self.current_time >= self.data.delays[self.current] {
             self.current = (self.current + 1) % self.data.frames.len();
             self.current_time = 0;
         }
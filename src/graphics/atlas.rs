@@ -3,8 +3,11 @@ extern crate futures;
 use error::QuicksilverError;
 use futures::{Async, Future, Poll};
 use futures::future::{JoinAll, join_all};
-use geom::{Positioned, Rectangle, Vector};
-use graphics::{Image, ImageLoader, ImageError};
+use geom::{Rectangle, Vector};
+use graphics::{Animation, Image, ImageLoader, ImageError};
+#[cfg(feature="atlas_json")]
+use graphics::atlas_json::{self, Format};
+use graphics::image::Rotation;
 use FileLoader;
 use std::{
     cmp::Ordering,
@@ -14,44 +17,114 @@ use std::{
     io::Error as IOError,
     num::ParseIntError,
     path::Path,
-    str::{FromStr, ParseBoolError, Split}
+    str::{FromStr, ParseBoolError, Split},
+    string::FromUtf8Error
 };
+use timer::check_tick_rate;
 
+// A named image within one of the atlas pages, and the frame duration in milliseconds if the
+// format has one
 #[derive(Clone)]
-struct Region {
-    image: usize,
-    name: String,
-    rotate: bool,
-    region: Rectangle,
-    center: Vector,
-    index: i32
+pub(crate) struct Region {
+    pub image: usize,
+    pub name: String,
+    pub rotate: Rotation,
+    pub region: Rectangle,
+    pub offset: Vector,
+    pub original_size: Vector,
+    pub duration: Option<u32>,
+    pub index: i32
 }
 
+// The frame duration of images from formats that don't have them, which is also Aseprite's default
+const DEFAULT_DURATION: u32 = 100;
+
 #[derive(Clone, Debug)]
 /// An image atlas that allows a single image file to represent multiple individual images
 ///
-/// It uses the libgdx / spine atlas format and groups them by name and index if applicable 
+/// It uses the libgdx / spine atlas format and groups them by name and index if applicable. 
+/// TexturePacker and Aseprite JSON exports can be loaded too, with the `atlas_json` feature.
 pub struct Atlas {
-    data: HashMap<String, AtlasItem>
+    data: HashMap<String, AtlasItem>,
+    durations: HashMap<String, Vec<u32>>
 }
 
 impl Atlas {
     /// Load an atlas at a given path
     pub fn load<'a, P: 'static + AsRef<Path>>(path: P) -> AtlasLoader {
         AtlasLoader(Box::new(FileLoader::load(path.as_ref())
-            .map(|bytes| String::from_utf8(bytes).unwrap())
+            .and_then(|bytes| Ok(String::from_utf8(bytes).map_err(AtlasError::from)?))
             .and_then(|data| parse(data, path))
             .map(create)))
     }
 
+    /// Load a TexturePacker atlas exported as JSON (Hash) or JSON (Array)
+    ///
+    /// Every frame is a still image, named by its filename in the atlas
+    #[cfg(feature="atlas_json")]
+    pub fn load_texture_packer<P: 'static + AsRef<Path>>(path: P) -> AtlasLoader {
+        Atlas::load_json(path, Format::TexturePacker)
+    }
+
+    /// Load an Aseprite sprite sheet exported as JSON, in either the hash or array layout
+    ///
+    /// Every frame is a still image named by its filename, and every frame tag is an animation of
+    /// its frames in the tag's direction, which keeps the frames' durations for `Atlas::animation`
+    #[cfg(feature="atlas_json")]
+    pub fn load_aseprite<P: 'static + AsRef<Path>>(path: P) -> AtlasLoader {
+        Atlas::load_json(path, Format::Aseprite)
+    }
+
+    #[cfg(feature="atlas_json")]
+    fn load_json<P: 'static + AsRef<Path>>(path: P, format: Format) -> AtlasLoader {
+        AtlasLoader(Box::new(FileLoader::load(path.as_ref())
+            .and_then(|bytes| Ok(String::from_utf8(bytes).map_err(AtlasError::from)?))
+            .and_then(move |data| atlas_json::parse(data, path, format))
+            .map(create)))
+    }
+
+    /// Create an atlas from the text of a libgdx manifest and the images of its pages
+    ///
+    /// The pages are given in the order the manifest lists them. This is for atlases that are
+    /// already in memory, for example embedded with `include_str!`.
+    pub fn from_manifest(manifest: &str, pages: Vec<Image>) -> Result<Atlas, AtlasError> {
+        let (names, regions) = parse_manifest(manifest).map_err(AtlasError::ParseError)?;
+        if pages.len() != names.len() {
+            return Err(AtlasError::ParseError("The number of pages doesn't match the manifest"));
+        }
+        Ok(create((pages, regions)))
+    }
+
     /// Get an image or animation with a given name
     pub fn get(&self, name: &str) -> Option<AtlasItem> {
         Some(self.data.get(name)?.clone())
     }
+
+    /// Get how long each frame of an item is shown for, in milliseconds
+    ///
+    /// Only Aseprite atlases store frame durations, other formats use 100 milliseconds a frame
+    pub fn durations(&self, name: &str) -> Option<&[u32]> {
+        Some(self.durations.get(name)?.as_slice())
+    }
+
+    /// Create an animation from an item, with the frames' durations turned into update ticks
+    ///
+    /// The update rate is the length of an update in milliseconds, like
+    /// `WindowBuilder::with_update_rate`, so it must be positive. Every frame lasts at least one tick.
+    pub fn animation(&self, name: &str, update_rate: f64) -> Option<Animation> {
+        check_tick_rate(update_rate);
+        let frames = match self.get(name)? {
+            AtlasItem::Image(image) => vec![image],
+            AtlasItem::Animation(frames) => frames
+        };
+        let ticks = self.durations(name)?.iter()
+            .map(|&duration| ((duration as f64 / update_rate).round() as u32).max(1));
+        Some(Animation::from_frames(frames.into_iter().zip(ticks)))
+    }
 }
 
-type ManifestContents = Result<(JoinAll<Vec<ImageLoader>>, Vec<Region>), &'static str>;
-struct ManifestLoader(ManifestContents);
+pub(crate) type ManifestContents = Result<(JoinAll<Vec<ImageLoader>>, Vec<Region>), &'static str>;
+pub(crate) struct ManifestLoader(pub ManifestContents);
 
 /// A Future to load an Atlas
 pub struct AtlasLoader(Box<Future<Item=Atlas, Error=QuicksilverError>>);
@@ -82,16 +155,23 @@ impl Future for AtlasLoader {
 }
 
 // Turn the pages and regions into an Atlas
-fn create(data: (Vec<Image>, Vec<Region>)) -> Atlas {
+pub(crate) fn create(data: (Vec<Image>, Vec<Region>)) -> Atlas {
     let (images, regions) = data;
     let mut images = regions.into_iter()
-        .map(|region| 
-             (region.name, region.index, images[region.image].subimage(region.region)))
-        .collect::<Vec<(String, i32, Image)>>();
+        .map(|region| {
+            let image = images[region.image].subimage(region.region)
+                .with_packing(region.rotate, region.offset, region.original_size);
+            (region.name, region.index, image, region.duration.unwrap_or(DEFAULT_DURATION))
+        })
+        .collect::<Vec<(String, i32, Image, u32)>>();
     //Sort the images by name, and then sub-sort them by index
-    images.sort_by(|a: &(String, i32, Image), b: &(String, i32, Image)| match a.0.cmp(&b.0) { Ordering::Equal => a.1.cmp(&b.1), x => x });
+    images.sort_by(|a: &(String, i32, Image, u32), b: &(String, i32, Image, u32)| match a.0.cmp(&b.0) { Ordering::Equal => a.1.cmp(&b.1), x => x });
+    let mut durations: HashMap<String, Vec<u32>> = HashMap::new();
+    for item in images.iter() {
+        durations.entry(item.0.clone()).or_insert_with(Vec::new).push(item.3);
+    }
     let data = images.into_iter()
-        .fold(Vec::new(), |mut list: Vec<(String, AtlasItem)>, item: (String, i32, Image)| {
+        .fold(Vec::new(), |mut list: Vec<(String, AtlasItem)>, item: (String, i32, Image, u32)| {
             let len = list.len();
             // There are no previous items or the previous item is a different name
             if len == 0 || list[len - 1].0 != item.0 {
@@ -114,14 +194,24 @@ fn create(data: (Vec<Image>, Vec<Region>)) -> Atlas {
             }
             list
         }).into_iter().collect::<HashMap<String, AtlasItem>>();
-    Atlas { data }
+    Atlas { data, durations }
 }
 
 // Parse a manifest file into a future to load the contents of the atlas
 fn parse<P: AsRef<Path>>(data: String, path: P) -> ManifestLoader {
-    return ManifestLoader(parse_body(data, path.as_ref())); 
-    fn parse_body(data: String, path: &Path) -> ManifestContents {
-        use std::path::PathBuf;
+    let path = path.as_ref();
+    let directory: &Path = if let Some(parent) = path.parent() { parent } else { path };
+    ManifestLoader(parse_manifest(&data).map(|(pages, regions)| {
+        //Load the pages relative to the atlas location
+        let images = pages.iter().map(|page| Image::load(directory.join(page))).collect();
+        (join_all(images), regions)
+    }))
+}
+
+// Parse a manifest into the file names of its pages and the regions on them
+fn parse_manifest(data: &str) -> Result<(Vec<String>, Vec<Region>), &'static str> {
+    return parse_body(data);
+    fn parse_body(data: &str) -> Result<(Vec<String>, Vec<Region>), &'static str> {
         use std::iter::Map as IterMap;
         // Split a line into its right-hand values
         fn get_values_from_line<'a, T: FromStr>(line: &'a str) -> IterMap<Split<'a, &'static str>, fn(&'a str) -> T> 
@@ -144,11 +234,8 @@ fn parse<P: AsRef<Path>>(data: String, path: P) -> ManifestLoader {
         let mut lines = data.lines();
         let mut images = Vec::new();
         let mut regions = Vec::new();
-        let directory: &Path = if let Some(parent) = path.parent() { parent } else { path.as_ref() };
         while let Some(line) = lines.next() {
-            //Create a path relative to the atlas location
-            let path: PathBuf = [directory, &Path::new(line)].iter().collect();
-            images.push(Image::load(path));
+            images.push(line.to_owned());
             //Skip some lines the loader doesn't use
             for _ in 0..4 {
                 getval(&mut lines)?;
@@ -160,7 +247,7 @@ fn parse<P: AsRef<Path>>(data: String, path: P) -> ManifestLoader {
                 let name = line.to_owned();
                 let mut rotate = get_values_from_line(getval(&mut lines)?);
                 let mut xy = get_values_from_line::<i32>(getval(&mut lines)?);
-                let mut size = get_values_from_line(getval(&mut lines)?);
+                let mut size = get_values_from_line::<i32>(getval(&mut lines)?);
                 let mut line = getval(&mut lines)?;
                 while !line.contains("orig") {
                     line = getval(&mut lines)?;
@@ -168,16 +255,21 @@ fn parse<P: AsRef<Path>>(data: String, path: P) -> ManifestLoader {
                 let mut orig = get_values_from_line::<i32>(line);
                 let mut offset = get_values_from_line::<i32>(getval(&mut lines)?);
                 let index = getval(&mut get_values_from_line(getval(&mut lines)?))?;
-                let rotate = getval(&mut rotate)?;
-                let region = Rectangle::new(getval(&mut xy)?, getval(&mut xy)?, getval(&mut size)?, getval(&mut size)?);
+                let rotate = if getval(&mut rotate)? { Rotation::CounterClockwise } else { Rotation::Upright };
+                let position = Vector::new(getval(&mut xy)?, getval(&mut xy)?);
+                let size = Vector::new(getval(&mut size)?, getval(&mut size)?);
                 let original_size = Vector::new(getval(&mut orig)?, getval(&mut orig)?);
                 let offset = Vector::new(getval(&mut offset)?, getval(&mut offset)?);
-                let center = region.center() + (original_size - region.size() - offset.x_comp() + offset.y_comp());
+                //The size is of the upright image, so a rotated region is packed with it swapped
+                let packed_size = if rotate != Rotation::Upright { Vector::new(size.y, size.x) } else { size };
+                let region = Rectangle::newv(position, packed_size);
+                //The offset is measured from the bottom left of the original image
+                let offset = Vector::new(offset.x, original_size.y - size.y - offset.y);
                 let image = images.len() - 1;
-                regions.push(Region { image, name, region, rotate, center, index });
+                regions.push(Region { image, name, region, rotate, offset, original_size, duration: None, index });
             }
         }
-        Ok((join_all(images), regions))
+        Ok((images, regions))
     }
 }
 
@@ -220,7 +312,9 @@ pub enum AtlasError {
     /// An error created parsing the Atlas manifest
     ParseError(&'static str),
     /// An error created loading the atlas manifest
-    IOError(IOError)
+    IOError(IOError),
+    /// An error created packing an atlas, when the named image is larger than a page
+    TooLarge(String)
 }
 
 impl Display for AtlasError {
@@ -234,7 +328,8 @@ impl Error for AtlasError {
         match self {
             &AtlasError::ImageError(ref err) => err.description(),
             &AtlasError::ParseError(string) => string,
-            &AtlasError::IOError(ref err) => err.description()
+            &AtlasError::IOError(ref err) => err.description(),
+            &AtlasError::TooLarge(_) => "An image is too large to fit on an atlas page"
         }
     }
     
@@ -242,7 +337,8 @@ impl Error for AtlasError {
         match self {
             &AtlasError::ImageError(ref err) => Some(err),
             &AtlasError::ParseError(_) => None,
-            &AtlasError::IOError(ref err) => Some(err)
+            &AtlasError::IOError(ref err) => Some(err),
+            &AtlasError::TooLarge(_) => None
         }
     }
 }
@@ -269,9 +365,85 @@ impl From<ParseBoolError> for AtlasError {
     }
 }
 
+#[doc(hidden)]
+impl From<FromUtf8Error> for AtlasError {
+    fn from(_: FromUtf8Error) -> AtlasError {
+        AtlasError::ParseError("The atlas isn't valid UTF-8 text")
+    }
+}
+
 #[doc(hidden)]
 impl From<IOError> for AtlasError {
     fn from(err: IOError) -> AtlasError {
         AtlasError::IOError(err)
     }
 }
+
+#[cfg(all(test, not(target_arch="wasm32")))]
+mod tests {
+    use super::*;
+    use ffi::gl;
+    use graphics::PixelFormat;
+
+    #[test]
+    fn rotated_trimmed_region() {
+        gl::set_headless(true);
+        let page = Image::new_null(64, 64, PixelFormat::RGBA);
+        // A 10x4 sprite trimmed from 16x8, packed on its side at (8, 16)
+        let region = Region {
+            image: 0,
+            name: "sprite".to_owned(),
+            rotate: Rotation::CounterClockwise,
+            region: Rectangle::new(8, 16, 4, 10),
+            offset: Vector::new(2, 3),
+            original_size: Vector::new(16, 8),
+            duration: None,
+            index: -1
+        };
+        let image = create((vec![page], vec![region])).get("sprite").unwrap().unwrap_image();
+        assert!(image.is_rotated());
+        assert_eq!(image.original_size(), Vector::new(16, 8));
+        assert_eq!(image.trimmed_area(), Rectangle::new(2, 3, 10, 4));
+        // Turned counter-clockwise, the upright top left is packed bottom left and the top right packed top left
+        assert_eq!(image.tex_coord(Vector::zero()), Vector::new(8, 26) / 64);
+        assert_eq!(image.tex_coord(Vector::new(10, 0)), Vector::new(8, 16) / 64);
+        // Turned clockwise, the upright top left is packed top right instead
+        let clockwise = image.with_packing(Rotation::Clockwise, Vector::zero(), Vector::new(10, 4));
+        assert_eq!(clockwise.tex_coord(Vector::zero()), Vector::new(12, 16) / 64);
+    }
+
+    #[test]
+    fn frame_durations() {
+        gl::set_headless(true);
+        let page = Image::new_null(64, 64, PixelFormat::RGBA);
+        let frame = |index, duration| Region {
+            image: 0,
+            name: "walk".to_owned(),
+            rotate: Rotation::Upright,
+            region: Rectangle::new(index * 8, 0, 8, 8),
+            offset: Vector::zero(),
+            original_size: Vector::new(8, 8),
+            duration,
+            index
+        };
+        let atlas = create((vec![page], vec![frame(1, Some(50)), frame(0, Some(10))]));
+        assert_eq!(atlas.durations("walk"), Some(&[10, 50][..]));
+        let mut animation = atlas.animation("walk", 25.0).unwrap();
+        assert_eq!(animation.current_frame().area().x, 0.0);
+        animation.tick();
+        assert_eq!(animation.current_frame().area().x, 8.0);
+        animation.tick();
+        assert_eq!(animation.current_frame().area().x, 8.0);
+        animation.tick();
+        assert_eq!(animation.current_frame().area().x, 0.0);
+        assert!(atlas.animation("run", 25.0).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_update_rate() {
+        gl::set_headless(true);
+        let atlas = create((Vec::new(), Vec::new()));
+        atlas.animation("walk", 0.0);
+    }
+}
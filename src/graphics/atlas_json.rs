@@ -0,0 +1,244 @@
+extern crate futures;
+extern crate serde;
+extern crate serde_json;
+
+use futures::future::join_all;
+use geom::{Rectangle, Vector};
+use graphics::Image;
+use graphics::atlas::{ManifestContents, ManifestLoader, Region};
+use graphics::image::Rotation;
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::{
+    fmt::{Formatter, Result as FmtResult},
+    path::{Path, PathBuf}
+};
+
+// The JSON dialect an atlas was exported in
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Format {
+    // TexturePacker's JSON (Hash) and JSON (Array), which rotate sprites clockwise
+    TexturePacker,
+    // Aseprite's sprite sheets, which have frame durations and tags
+    Aseprite
+}
+
+#[derive(Deserialize)]
+struct JsonRect {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32
+}
+
+#[derive(Deserialize)]
+struct JsonSize {
+    w: i32,
+    h: i32
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonFrame {
+    #[serde(default)]
+    filename: String,
+    frame: JsonRect,
+    #[serde(default)]
+    rotated: bool,
+    sprite_source_size: Option<JsonRect>,
+    source_size: Option<JsonSize>,
+    duration: Option<u32>
+}
+
+// The frames are either a list or a map from their names, which has to keep its order because
+// Aseprite tags refer to frames by their position
+struct JsonFrames(Vec<JsonFrame>);
+
+impl<'de> Deserialize<'de> for JsonFrames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<JsonFrames, D::Error> {
+        struct FramesVisitor;
+
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = JsonFrames;
+
+            fn expecting(&self, f: &mut Formatter) -> FmtResult {
+                write!(f, "a list or map of frames")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<JsonFrames, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(frame) = seq.next_element()? {
+                    frames.push(frame);
+                }
+                Ok(JsonFrames(frames))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<JsonFrames, A::Error> {
+                let mut frames = Vec::new();
+                while let Some((filename, frame)) = map.next_entry::<String, JsonFrame>()? {
+                    frames.push(JsonFrame { filename, ..frame });
+                }
+                Ok(JsonFrames(frames))
+            }
+        }
+
+        deserializer.deserialize_any(FramesVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonMeta {
+    image: String,
+    #[serde(default)]
+    frame_tags: Vec<JsonTag>
+}
+
+#[derive(Deserialize)]
+struct JsonAtlas {
+    frames: JsonFrames,
+    meta: JsonMeta
+}
+
+// Parse a JSON atlas into a future to load its image and regions
+pub(crate) fn parse<P: AsRef<Path>>(data: String, path: P, format: Format) -> ManifestLoader {
+    ManifestLoader(parse_body(&data, path.as_ref(), format))
+}
+
+fn parse_body(data: &str, path: &Path, format: Format) -> ManifestContents {
+    let (image, regions) = parse_regions(data, format)?;
+    //Find the image relative to the atlas location
+    let directory = path.parent().unwrap_or(path);
+    let image: PathBuf = [directory, Path::new(&image)].iter().collect();
+    Ok((join_all(vec![Image::load(image)]), regions))
+}
+
+// Parse the name of the atlas image and the regions within it
+fn parse_regions(data: &str, format: Format) -> Result<(String, Vec<Region>), &'static str> {
+    let atlas: JsonAtlas = serde_json::from_str(data).map_err(|_| "Failed to parse the atlas JSON")?;
+    let rotation = match format {
+        Format::TexturePacker => Rotation::Clockwise,
+        Format::Aseprite => Rotation::CounterClockwise
+    };
+    let frames: Vec<Region> = atlas.frames.0.into_iter().map(|frame| {
+        let size = Vector::new(frame.frame.w, frame.frame.h);
+        //The size is of the upright image, so a rotated frame is packed with it swapped
+        let packed_size = if frame.rotated { Vector::new(size.y, size.x) } else { size };
+        let offset = frame.sprite_source_size.map(|trim| Vector::new(trim.x, trim.y)).unwrap_or(Vector::zero());
+        let original_size = frame.source_size.map(|original| Vector::new(original.w, original.h)).unwrap_or(size);
+        Region {
+            image: 0,
+            name: frame.filename,
+            rotate: if frame.rotated { rotation } else { Rotation::Upright },
+            region: Rectangle::newv(Vector::new(frame.frame.x, frame.frame.y), packed_size),
+            offset,
+            original_size,
+            duration: frame.duration,
+            index: -1
+        }
+    }).collect();
+    let mut regions = Vec::new();
+    if format == Format::Aseprite {
+        //Every tag becomes an animation of its frames, played in the tag's direction
+        for tag in atlas.meta.frame_tags.iter() {
+            if tag.from > tag.to || tag.to >= frames.len() {
+                return Err("An atlas frame tag refers to frames that don't exist");
+            }
+            let forward: Vec<usize> = (tag.from..tag.to + 1).collect();
+            let order: Vec<usize> = match tag.direction.as_str() {
+                "reverse" => forward.into_iter().rev().collect(),
+                // A ping-pong doesn't repeat the frames at either end when it turns around
+                "pingpong" => forward.iter().cloned()
+                    .chain(forward.iter().cloned().rev().skip(1).take(forward.len().saturating_sub(2)))
+                    .collect(),
+                _ => forward
+            };
+            for (index, &frame) in order.iter().enumerate() {
+                regions.push(Region { name: tag.name.clone(), index: index as i32, ..frames[frame].clone() });
+            }
+        }
+    }
+    regions.extend(frames);
+    Ok((atlas.meta.image, regions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_packer_hash() {
+        let json = r#"{
+            "frames": {
+                "player.png": {
+                    "frame": {"x": 2, "y": 4, "w": 10, "h": 6},
+                    "rotated": true,
+                    "trimmed": true,
+                    "spriteSourceSize": {"x": 1, "y": 3, "w": 10, "h": 6},
+                    "sourceSize": {"w": 12, "h": 10}
+                },
+                "coin.png": {
+                    "frame": {"x": 20, "y": 4, "w": 8, "h": 8},
+                    "rotated": false,
+                    "trimmed": false,
+                    "spriteSourceSize": {"x": 0, "y": 0, "w": 8, "h": 8},
+                    "sourceSize": {"w": 8, "h": 8}
+                }
+            },
+            "meta": {"image": "sheet.png", "size": {"w": 32, "h": 32}}
+        }"#;
+        let (image, regions) = parse_regions(json, Format::TexturePacker).unwrap();
+        assert_eq!(image, "sheet.png");
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].name, "player.png");
+        assert_eq!(regions[0].rotate, Rotation::Clockwise);
+        assert_eq!(regions[0].region, Rectangle::new(2, 4, 6, 10));
+        assert_eq!(regions[0].offset, Vector::new(1, 3));
+        assert_eq!(regions[0].original_size, Vector::new(12, 10));
+        assert_eq!(regions[1].region, Rectangle::new(20, 4, 8, 8));
+    }
+
+    #[test]
+    fn aseprite_tags() {
+        let json = r#"{
+            "frames": [
+                {"filename": "hero 0", "frame": {"x": 0, "y": 0, "w": 8, "h": 8}, "duration": 100},
+                {"filename": "hero 1", "frame": {"x": 8, "y": 0, "w": 8, "h": 8}, "duration": 50},
+                {"filename": "hero 2", "frame": {"x": 16, "y": 0, "w": 8, "h": 8}, "duration": 200}
+            ],
+            "meta": {
+                "image": "hero.png",
+                "frameTags": [
+                    {"name": "run", "from": 0, "to": 2, "direction": "pingpong"},
+                    {"name": "back", "from": 1, "to": 2, "direction": "reverse"}
+                ]
+            }
+        }"#;
+        let (_, regions) = parse_regions(json, Format::Aseprite).unwrap();
+        let run: Vec<(i32, Option<u32>)> = regions.iter()
+            .filter(|region| region.name == "run")
+            .map(|region| (region.index, region.duration))
+            .collect();
+        assert_eq!(run, vec![(0, Some(100)), (1, Some(50)), (2, Some(200)), (3, Some(50))]);
+        let back: Vec<f32> = regions.iter().filter(|region| region.name == "back").map(|region| region.region.x).collect();
+        assert_eq!(back, vec![16.0, 8.0]);
+        assert!(regions.iter().any(|region| region.name == "hero 1" && region.index == -1));
+    }
+
+    #[test]
+    fn bad_tag() {
+        let json = r#"{
+            "frames": [{"filename": "a", "frame": {"x": 0, "y": 0, "w": 1, "h": 1}}],
+            "meta": {"image": "a.png", "frameTags": [{"name": "walk", "from": 0, "to": 3}]}
+        }"#;
+        assert!(parse_regions(json, Format::Aseprite).is_err());
+    }
+}
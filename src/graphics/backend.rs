@@ -1,12 +1,13 @@
-use geom::Vector;
-use graphics::{Color, GpuTriangle, Image, PixelFormat, Vertex};
+use geom::{Transform, Vector};
+use graphics::{Color, GpuTriangle, Image, ImageScaleStrategy, Mesh, PixelFormat, Shader, Vertex};
+use graphics::image::set_default_scale_strategy;
+use graphics::shader::{ATTRIBUTES, DEFAULT_FRAGMENT_SHADER, DEFAULT_VERTEX_SHADER, MAX_BATCH_TEXTURES, MESH_VERTEX_SHADER};
 use ffi::gl;
 use std::{
-    ffi::CString,
-    mem::size_of,
+    collections::HashMap,
+    mem::{replace, size_of},
     os::raw::c_void,
-    ptr::null,
-    str::from_utf8
+    ptr::null
 };
 
 #[repr(u32)]
@@ -34,80 +35,29 @@ pub enum BlendMode {
 }
 
 pub(crate) struct Backend {
-    texture: u32,
     vertices: Vec<f32>,
-    indices: Vec<u32>, 
     null: Image, 
     vertex_length: usize, 
     index_length: usize, 
-    shader: u32, 
-    fragment: u32, 
-    vertex: u32, 
+    default_shader: Shader,
+    shader: Option<Shader>,
+    mesh_shader: Shader,
     vbo: u32, 
     ebo: u32, 
     vao: u32, 
-    texture_location: i32,
-    texture_mode: u32
+    headless: bool,
+    draw_calls: u32,
+    texture_switches: u32,
+    draw_calls_saved: u32
 }
 
-#[cfg(not(target_arch="wasm32"))]
-const DEFAULT_VERTEX_SHADER: &str = r#"#version 150
-in vec2 position;
-in vec2 tex_coord;
-in vec4 color;
-in float uses_texture;
-out vec4 Color;
-out vec2 Tex_coord;
-out float Uses_texture;
-void main() {
-    Color = color;
-    Tex_coord = tex_coord;
-    Uses_texture = uses_texture;
-    gl_Position = vec4(position, 0, 1);
-}"#;
-
-#[cfg(not(target_arch="wasm32"))]
-const DEFAULT_FRAGMENT_SHADER: &str = r#"#version 150
-in vec4 Color;
-in vec2 Tex_coord;
-in float Uses_texture;
-out vec4 outColor;
-uniform sampler2D tex;
-void main() {
-    vec4 tex_color = (Uses_texture != 0) ? texture(tex, Tex_coord) : vec4(1, 1, 1, 1);
-    outColor = Color * tex_color;
-}"#;
-
-#[cfg(target_arch="wasm32")]
-const DEFAULT_VERTEX_SHADER: &str = r#"attribute vec2 position;
-attribute vec2 tex_coord;
-attribute vec4 color;
-attribute lowp float uses_texture;
-varying vec2 Tex_coord;
-varying vec4 Color;
-varying lowp float Uses_texture;
-void main() {
-    gl_Position = vec4(position, 0, 1);
-    Tex_coord = tex_coord;
-    Color = color;
-    Uses_texture = uses_texture;
-}"#;
-
-#[cfg(target_arch="wasm32")]
-const DEFAULT_FRAGMENT_SHADER: &str = r#"varying highp vec4 Color;
-varying highp vec2 Tex_coord;
-varying lowp float Uses_texture;
-uniform sampler2D tex;
-void main() {
-    highp vec4 tex_color = (int(Uses_texture) != 0) ? texture2D(tex, Tex_coord) : vec4(1, 1, 1, 1);
-    gl_FragColor = Color * tex_color;
-}"#;
-
-pub(crate) const VERTEX_SIZE: usize = 9; // the number of floats in a vertex
+pub(crate) const VERTEX_SIZE: usize = 10; // the number of floats in a vertex
 
 impl Backend {
-    pub fn new(texture_mode: u32) -> Backend { 
-        let (vao, vbo, ebo) = unsafe {
+    pub fn new(scale_strategy: ImageScaleStrategy) -> Backend { 
+        let headless = gl::is_headless();
+        set_default_scale_strategy(scale_strategy);
+        let (vao, vbo, ebo) = if headless { (0, 0, 0) } else { unsafe {
             let vao = gl::GenVertexArray();
             gl::BindVertexArray(vao);
             let vbo = gl::GenBuffer();
@@ -117,195 +67,174 @@ impl Backend {
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
             gl::Enable( gl::BLEND );
             (vao, vbo, ebo)
-        };
+        } };
         let null = Image::new_null(1, 1, PixelFormat::RGBA);
-        let texture = null.get_id();
-        let mut backend = Backend {
-            texture,
+        let default_shader = Shader::new(DEFAULT_VERTEX_SHADER, DEFAULT_FRAGMENT_SHADER)
+            .expect("The default shader failed to compile");
+        let mesh_shader = Shader::new(MESH_VERTEX_SHADER, DEFAULT_FRAGMENT_SHADER)
+            .expect("The mesh shader failed to compile");
+        if !headless {
+            unsafe { gl::UseProgram(default_shader.program()) };
+        }
+        Backend {
             vertices: Vec::with_capacity(1024),
-            indices: Vec::with_capacity(1024), 
             null,
             vertex_length: 0, 
             index_length: 0, 
-            shader: 0, 
-            fragment: 0, 
-            vertex: 0, 
+            default_shader,
+            shader: None,
+            mesh_shader,
             vbo, 
             ebo, 
             vao, 
-            texture_location: 0,
-            texture_mode
-        };
-        backend.set_shader(DEFAULT_VERTEX_SHADER, DEFAULT_FRAGMENT_SHADER);
-        backend
+            headless,
+            draw_calls: 0,
+            texture_switches: 0,
+            draw_calls_saved: 0
+        }
     }
 
-    
-    fn set_shader(&mut self, vertex_shader: &str, fragment_shader: &str) {
-        unsafe {
-            if self.shader != 0 {
-                gl::DeleteProgram(self.shader);
-            }
-            if self.vertex != 0 {
-                gl::DeleteShader(self.vertex);
-            }
-            if self.fragment != 0 {
-                gl::DeleteShader(self.fragment);
-            }
-            self.vertex = gl::CreateShader(gl::VERTEX_SHADER);
-            let vertex_text = CString::new(vertex_shader).unwrap().into_raw();
-            gl::ShaderSource(self.vertex, vertex_text);
-            gl::CompileShader(self.vertex);
-            let mut status: i32 = 0;
-            gl::GetShaderiv(self.vertex, gl::COMPILE_STATUS, &mut status as *mut i32);
-            if status as u8 != gl::TRUE {
-                println!("Vertex shader compilation failed.");
-                let buffer: [u8; 512] = [0; 512];
-                let mut length = 0;
-                gl::GetShaderInfoLog(self.vertex, 512, &mut length as *mut i32, buffer.as_ptr() as *mut i8);
-                println!("Error: {}", from_utf8(&buffer).unwrap());
-            }
-            self.fragment = gl::CreateShader(gl::FRAGMENT_SHADER);
-            let fragment_text = CString::new(fragment_shader).unwrap().into_raw();
-            gl::ShaderSource(self.fragment, fragment_text);
-            gl::CompileShader(self.fragment);
-            gl::GetShaderiv(self.fragment, gl::COMPILE_STATUS, &mut status as *mut i32);
-            if status as u8 != gl::TRUE {
-                println!("Fragment shader compilation failed.");
-                let buffer: [u8; 512] = [0; 512];
-                let mut length = 0;
-                gl::GetShaderInfoLog(self.fragment, 512, &mut length as *mut i32, buffer.as_ptr() as *mut i8);
-                println!("Error: {}", from_utf8(&buffer).unwrap());
-            }
-            self.shader = gl::CreateProgram();
-            gl::AttachShader(self.shader, self.vertex);
-            gl::AttachShader(self.shader, self.fragment);
-            #[cfg(not(target_arch="wasm32"))] {
-                let raw = CString::new("out_color").unwrap().into_raw();
-                gl::BindFragDataLocation(self.shader, 0, raw as *mut i8);
-                CString::from_raw(raw);
-            }
-            gl::LinkProgram(self.shader);
-            gl::UseProgram(self.shader);
-            #[cfg(not(target_arch="wasm32"))] {
-                CString::from_raw(vertex_text);
-                CString::from_raw(fragment_text);
-            }
+    fn current_shader(&self) -> &Shader {
+        self.shader.as_ref().unwrap_or(&self.default_shader)
+    }
+
+    pub fn shader(&self) -> Option<Shader> {
+        self.shader.clone()
+    }
+
+    // Switch the program that future draws use, or go back to the default one
+    pub fn set_shader(&mut self, shader: Option<Shader>) {
+        self.shader = shader;
+        if !self.headless {
+            unsafe { gl::UseProgram(self.current_shader().program()) };
         }
     }
 
+    // The number of textures a single batch can use with the current shader
+    fn texture_units(&self) -> usize {
+        match self.shader {
+            Some(ref shader) => shader.texture_units(),
+            None => MAX_BATCH_TEXTURES
+        }
+    }
 
-    pub fn switch_texture(&mut self, texture: u32) {
-        if self.texture != self.null.get_id() && self.texture != texture {
-            self.flush();
+    // Upload all of the vertex data, growing the GPU buffer if it can't store it
+    unsafe fn upload_vertices(&mut self) {
+        let vertex_length = size_of::<f32>() * self.vertices.len();
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+        if vertex_length > self.vertex_length {
+            self.vertex_length = vertex_length * 2;
+            // Create the vertex array
+            gl::BufferData(gl::ARRAY_BUFFER, self.vertex_length as isize, null(), gl::STREAM_DRAW);
+            set_vertex_attributes();
         }
-        self.texture = texture;
+        let vertex_data = self.vertices.as_ptr() as *const c_void;
+        gl::BufferSubData(gl::ARRAY_BUFFER, 0, vertex_length as isize, vertex_data);
     }
 
-    pub fn flush(&mut self) {
-        if self.indices.len() != 0 {
-            unsafe {
-                // Check if the index buffer is big enough and upload the data
-                let index_length = size_of::<u32>() * self.indices.len();
-                let index_data = self.indices.as_ptr() as *const c_void;
-                if index_length > self.index_length {
-                    self.index_length = index_length * 2;
-                    gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, self.index_length as isize, null(), gl::STREAM_DRAW);
-                }
-                gl::BufferSubData(gl::ELEMENT_ARRAY_BUFFER, 0, index_length as isize, index_data);
-                // Upload the texture to the GPU
-                gl::ActiveTexture(gl::TEXTURE0);
-                if self.texture != 0 {
-                    gl::BindTexture(gl::TEXTURE_2D, self.texture);
-                    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, self.texture_mode as i32);
-                    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, self.texture_mode as i32);
-                }
-                gl::Uniform1i(self.texture_location, 0);
-                // Draw the triangles
-                gl::DrawElements(gl::TRIANGLES, self.indices.len() as i32, gl::UNSIGNED_INT, null());
+    // Upload the index data, growing the GPU buffer if it can't store it
+    unsafe fn upload_indices(&mut self, indices: &[u32]) {
+        let index_length = size_of::<u32>() * indices.len();
+        if index_length > self.index_length {
+            self.index_length = index_length * 2;
+            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, self.index_length as isize, null(), gl::STREAM_DRAW);
+        }
+        gl::BufferSubData(gl::ELEMENT_ARRAY_BUFFER, 0, index_length as isize, indices.as_ptr() as *const c_void);
+    }
+
+    // Bind textures to the units from the first one up, and point a shader's samplers at them
+    unsafe fn bind_textures(&self, textures: &[u32], shader: &Shader) {
+        let untextured = [self.null.get_id()];
+        let textures = if textures.is_empty() { &untextured[..] } else { textures };
+        for (unit, &texture) in textures.iter().enumerate() {
+            gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+        }
+        gl::ActiveTexture(gl::TEXTURE0);
+        for (unit, &location) in shader.texture_locations().iter().enumerate() {
+            gl::Uniform1i(location, unit as i32);
+        }
+    }
+
+    // Draw a mesh straight from its own buffers
+    pub fn draw_mesh(&mut self, mesh: &Mesh, transform: Transform, color: Color) {
+        if self.headless {
+            return;
+        }
+        self.mesh_shader.store_uniform("transform", transform.into());
+        self.mesh_shader.store_uniform("tint", color.into());
+        unsafe {
+            gl::UseProgram(self.mesh_shader.program());
+            self.mesh_shader.apply_uniforms();
+            gl::BindVertexArray(mesh.vao());
+            for batch in mesh.batches() {
+                self.bind_textures(&batch.textures, &self.mesh_shader);
+                gl::DrawElements(gl::TRIANGLES, batch.count as i32, gl::UNSIGNED_INT,
+                                 (batch.start * size_of::<u32>()) as *const c_void);
+                self.draw_calls += 1;
             }
-            self.indices.clear();
-            self.texture = self.null.get_id();
+            gl::BindVertexArray(self.vao);
+            gl::UseProgram(self.current_shader().program());
         }
-    } 
+    }
     
+    // Take the number of draw calls, texture switches and draw calls saved by batching textures
+    // since the last time they were taken
+    pub fn take_counts(&mut self) -> (u32, u32, u32) {
+        let counts = (self.draw_calls, self.texture_switches, self.draw_calls_saved);
+        self.draw_calls = 0;
+        self.texture_switches = 0;
+        self.draw_calls_saved = 0;
+        counts
+    }
+
     pub fn clear(&mut self, col: Color) {
+        if self.headless {
+            return;
+        }
         unsafe {
             gl::ClearColor(col.r, col.g, col.b, col.a);
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
         }
     }
 
+    // Draw sorted triangles, in as few draw calls as the textures they use allow
     pub fn draw(&mut self, vertices: &[Vertex], triangles: &[GpuTriangle]) {
-        // Turn the provided vertex data into stored vertex data
-        vertices.iter().for_each(|vertex| self.add_vertex(vertex));
-        let vertex_length = size_of::<f32>() * self.vertices.len();
-        // If the GPU can't store all of our data, re-create the GPU buffers so they can
-        if vertex_length > self.vertex_length {
-            unsafe {
-                self.vertex_length = vertex_length * 2;
-                // Create strings for all of the shader attributes
-                let position_string = CString::new("position").unwrap().into_raw();
-                let tex_coord_string = CString::new("tex_coord").unwrap().into_raw();
-                let color_string = CString::new("color").unwrap().into_raw();
-                let tex_string = CString::new("tex").unwrap().into_raw();
-                let use_texture_string = CString::new("uses_texture").unwrap().into_raw();
-                // Create the vertex array
-                gl::BufferData(gl::ARRAY_BUFFER, self.vertex_length as isize, null(), gl::STREAM_DRAW);
-                let stride_distance = (VERTEX_SIZE * size_of::<f32>()) as i32;
-                // Set up the vertex attributes
-                let pos_attrib = gl::GetAttribLocation(self.shader, position_string as *const i8) as u32;
-                gl::EnableVertexAttribArray(pos_attrib);
-                gl::VertexAttribPointer(pos_attrib, 2, gl::FLOAT, gl::FALSE, stride_distance, null());
-                let tex_attrib = gl::GetAttribLocation(self.shader, tex_coord_string as *const i8) as u32;
-                gl::EnableVertexAttribArray(tex_attrib);
-                gl::VertexAttribPointer(tex_attrib, 2, gl::FLOAT, gl::FALSE, stride_distance, (2 * size_of::<f32>()) as *const c_void);
-                let col_attrib = gl::GetAttribLocation(self.shader, color_string as *const i8) as u32;
-                gl::EnableVertexAttribArray(col_attrib);
-                gl::VertexAttribPointer(col_attrib, 4, gl::FLOAT, gl::FALSE, stride_distance, (4 * size_of::<f32>()) as *const c_void);
-                let use_texture_attrib = gl::GetAttribLocation(self.shader, use_texture_string as *const i8) as u32;
-                gl::EnableVertexAttribArray(use_texture_attrib);
-                gl::VertexAttribPointer(use_texture_attrib, 1, gl::FLOAT, gl::FALSE, stride_distance, (8 * size_of::<f32>()) as *const c_void);
-                self.texture_location = gl::GetUniformLocation(self.shader, tex_string as *const i8);
-                // Make sure to deallocate the attribute strings, except on WASM
-                // (WASM deallocates automatically)
-                #[cfg(not(target_arch="wasm32"))] {
-                    CString::from_raw(position_string);
-                    CString::from_raw(tex_coord_string);
-                    CString::from_raw(color_string);
-                    CString::from_raw(tex_string);
-                    CString::from_raw(use_texture_string);
-                }
-            }
+        if self.headless || triangles.is_empty() {
+            return;
         }
-        // Upload all of the vertex data
-        let vertex_data = self.vertices.as_ptr() as *const c_void;
-        unsafe { gl::BufferSubData(gl::ARRAY_BUFFER, 0, vertex_length as isize, vertex_data) };
-        // Scan through the triangles, adding the indices to the index buffer (every time the
-        // texture switches, flush and switch the bound texture)
-        for triangle in triangles.iter() {
-            if let Some(ref img) = triangle.image { self.switch_texture(img.get_id()); }
-            self.indices.extend(triangle.indices.iter());
-        }
-        // Flush any remaining triangles
-        self.flush();
+        let batched = batch_triangles(triangles.iter().map(|triangle| (triangle.indices, triangle.image.as_ref().map(Image::get_id))),
+                                      vertices.len(), self.texture_units());
+        // Turn the provided vertex data into stored vertex data, followed by the copies of any
+        // vertices shared between texture units
         self.vertices.clear();
-    }
-
-    pub fn add_vertex(&mut self, vertex: &Vertex) {
-        self.vertices.push(vertex.pos.x);
-        self.vertices.push(vertex.pos.y);
-        let tex_pos = vertex.tex_pos.unwrap_or(Vector::zero());
-        self.vertices.push(tex_pos.x);
-        self.vertices.push(tex_pos.y);
-        self.vertices.push(vertex.col.r);
-        self.vertices.push(vertex.col.g);
-        self.vertices.push(vertex.col.b);
-        self.vertices.push(vertex.col.a);
-        self.vertices.push(if vertex.tex_pos.is_some() { 1f32 } else { 0f32 });
+        let sources = (0..vertices.len()).chain(batched.copies.iter().cloned());
+        for (source, &unit) in sources.zip(batched.units.iter()) {
+            write_vertex(&mut self.vertices, &vertices[source], unit);
+        }
+        unsafe {
+            // Meshes bind their own vertex arrays, so make sure the batch's is bound
+            gl::BindVertexArray(self.vao);
+            self.upload_vertices();
+            self.upload_indices(&batched.indices);
+            let shader = self.current_shader();
+            shader.apply_uniforms();
+            for batch in batched.batches.iter() {
+                // Bind each texture in the batch to its unit, and point the samplers at them
+                self.bind_textures(&batch.textures, shader);
+                gl::DrawElements(gl::TRIANGLES, batch.count as i32, gl::UNSIGNED_INT,
+                                 (batch.start * size_of::<u32>()) as *const c_void);
+            }
+        }
+        self.draw_calls += batched.batches.len() as u32;
+        self.texture_switches += batched.batches.len() as u32 - 1;
+        self.draw_calls_saved += batched.saved;
     }
 
     pub fn set_blend_mode(&mut self, blend: BlendMode) {
+        if self.headless {
+            return;
+        }
         unsafe { 
             gl::BlendFunc(gl::ONE, gl::ONE);
             gl::BlendEquationSeparate(blend as u32, gl::FUNC_ADD);
@@ -313,6 +242,9 @@ impl Backend {
     }
 
     pub fn reset_blend_mode(&mut self) {
+        if self.headless {
+            return;
+        }
         unsafe {
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
             gl::BlendEquationSeparate(gl::FUNC_ADD, gl::FUNC_ADD);
@@ -322,10 +254,10 @@ impl Backend {
 
 impl Drop for Backend {
     fn drop(&mut self) { 
+        if self.headless {
+            return;
+        }
         unsafe {
-            gl::DeleteProgram(self.shader);
-            gl::DeleteShader(self.fragment);
-            gl::DeleteShader(self.vertex);
             gl::DeleteBuffer(self.vbo);
             gl::DeleteBuffer(self.ebo);
             gl::DeleteVertexArray(self.vao);
@@ -333,3 +265,202 @@ impl Drop for Backend {
     } 
 }
 
+
+// Add a vertex to a buffer in the layout the shaders expect, using the given texture unit
+pub(crate) fn write_vertex(buffer: &mut Vec<f32>, vertex: &Vertex, unit: f32) {
+    buffer.push(vertex.pos.x);
+    buffer.push(vertex.pos.y);
+    let tex_pos = vertex.tex_pos.unwrap_or(Vector::zero());
+    buffer.push(tex_pos.x);
+    buffer.push(tex_pos.y);
+    buffer.push(vertex.col.r);
+    buffer.push(vertex.col.g);
+    buffer.push(vertex.col.b);
+    buffer.push(vertex.col.a);
+    buffer.push(if vertex.tex_pos.is_some() { 1f32 } else { 0f32 });
+    buffer.push(unit);
+}
+
+// Point the vertex attributes at the bound vertex buffer, which every shader binds to the same locations
+pub(crate) unsafe fn set_vertex_attributes() {
+    let stride_distance = (VERTEX_SIZE * size_of::<f32>()) as i32;
+    let sizes = [2, 2, 4, 1, 1];
+    let mut offset = 0;
+    for location in 0..ATTRIBUTES.len() {
+        gl::EnableVertexAttribArray(location as u32);
+        gl::VertexAttribPointer(location as u32, sizes[location], gl::FLOAT, gl::FALSE, stride_distance, 
+                                (offset * size_of::<f32>()) as *const c_void);
+        offset += sizes[location] as usize;
+    }
+}
+
+// The textures bound to texture units for the batch being built
+pub(crate) struct TextureSlots {
+    bound: Vec<u32>
+}
+
+impl TextureSlots {
+    pub fn new() -> TextureSlots {
+        TextureSlots { bound: Vec::with_capacity(MAX_BATCH_TEXTURES) }
+    }
+
+    // Find the unit a texture is bound to, binding it to the next one if there are fewer than
+    // `units` in use
+    pub fn unit(&mut self, texture: u32, units: usize) -> Option<usize> {
+        match self.bound.iter().position(|&bound| bound == texture) {
+            Some(unit) => Some(unit),
+            None if self.bound.len() < units => {
+                self.bound.push(texture);
+                Some(self.bound.len() - 1)
+            }
+            None => None
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.bound.clear();
+    }
+}
+
+// A run of indices that is drawn in a single draw call, with the textures bound to its units
+pub(crate) struct Batch {
+    pub start: usize,
+    pub count: usize,
+    pub textures: Vec<u32>
+}
+
+// Sorted triangles split into batches
+pub(crate) struct Batches {
+    pub batches: Vec<Batch>,
+    pub indices: Vec<u32>,
+    // The texture unit of every vertex, followed by those of the copies
+    pub units: Vec<f32>,
+    // The vertex that each copy was made from
+    pub copies: Vec<usize>,
+    // The number of times the texture changed without a new batch
+    pub saved: u32
+}
+
+// Split sorted triangles, given as their indices and texture, into batches that each use at most
+// `texture_units` textures
+//
+// Each vertex only has room for one texture unit, so a vertex used by triangles whose textures are
+// bound to different units is copied, and the triangles are pointed at the copies.
+pub(crate) fn batch_triangles<I>(triangles: I, vertex_count: usize, texture_units: usize) -> Batches
+        where I: IntoIterator<Item = ([u32; 3], Option<u32>)> {
+    let mut batches = Vec::new();
+    let mut indices = Vec::new();
+    let mut vertex_units: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut copies = Vec::new();
+    let mut copy_indices = HashMap::new();
+    let mut slots = TextureSlots::new();
+    let mut previous = None;
+    let mut saved = 0;
+    let mut start = 0;
+    for (triangle, texture) in triangles {
+        let unit = texture.map(|texture| {
+            let unit = match slots.unit(texture, texture_units) {
+                Some(unit) => {
+                    if previous.map_or(false, |previous| previous != texture) {
+                        saved += 1;
+                    }
+                    unit
+                }
+                None => {
+                    batches.push(Batch { start, count: indices.len() - start, textures: replace(&mut slots.bound, Vec::new()) });
+                    start = indices.len();
+                    slots.unit(texture, texture_units).expect("An empty batch has a free texture unit")
+                }
+            };
+            previous = Some(texture);
+            unit
+        });
+        for &index in triangle.iter() {
+            let index = index as usize;
+            let index = match (unit, vertex_units[index]) {
+                (Some(unit), None) => {
+                    vertex_units[index] = Some(unit);
+                    index
+                }
+                (Some(unit), Some(current)) if current != unit => {
+                    *copy_indices.entry((index, unit)).or_insert_with(|| {
+                        copies.push(index);
+                        vertex_units.push(Some(unit));
+                        vertex_units.len() - 1
+                    })
+                }
+                _ => index
+            };
+            indices.push(index as u32);
+        }
+    }
+    if indices.len() > start {
+        batches.push(Batch { start, count: indices.len() - start, textures: slots.bound });
+    }
+    let units = vertex_units.iter().map(|unit| unit.unwrap_or(0) as f32).collect();
+    Batches { batches, indices, units, copies, saved }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_slots() {
+        let mut slots = TextureSlots::new();
+        assert_eq!(slots.unit(5, 2), Some(0));
+        assert_eq!(slots.unit(9, 2), Some(1));
+        assert_eq!(slots.unit(5, 2), Some(0));
+        assert_eq!(slots.unit(9, 2), Some(1));
+        assert_eq!(slots.unit(3, 2), None);
+        slots.clear();
+        assert_eq!(slots.unit(3, 2), Some(0));
+        assert_eq!(slots.unit(3, 1), Some(0));
+        assert_eq!(slots.unit(5, 1), None);
+    }
+
+    // The unit and texture that each index of a batched triangle ends up sampling
+    fn sampled(batched: &Batches, vertex_count: usize) -> Vec<(usize, u32)> {
+        batched.batches.iter().flat_map(|batch| {
+            batched.indices[batch.start..batch.start + batch.count].iter().map(move |&index| {
+                let source = if (index as usize) < vertex_count { index as usize } else { batched.copies[index as usize - vertex_count] };
+                let unit = batched.units[index as usize] as usize;
+                (source, batch.textures[unit])
+            })
+        }).collect()
+    }
+
+    #[test]
+    fn shared_vertices() {
+        // Two quads drawn from the same corners with different textures
+        let triangles = vec![([0, 1, 2], Some(7)), ([2, 3, 0], Some(7)), ([0, 1, 2], Some(9)), ([2, 3, 0], Some(9))];
+        let batched = batch_triangles(triangles, 4, 8);
+        assert_eq!(batched.batches.len(), 1);
+        assert_eq!(batched.saved, 1);
+        assert_eq!(batched.copies, vec![0, 1, 2, 3]);
+        assert_eq!(batched.indices, vec![0, 1, 2, 2, 3, 0, 4, 5, 6, 6, 7, 4]);
+        assert_eq!(sampled(&batched, 4), vec![(0, 7), (1, 7), (2, 7), (2, 7), (3, 7), (0, 7),
+                                               (0, 9), (1, 9), (2, 9), (2, 9), (3, 9), (0, 9)]);
+    }
+
+    #[test]
+    fn shared_vertex_batches() {
+        // A fan of triangles around a shared center, each with its own texture, which needs two batches
+        let triangles: Vec<_> = (0..10).map(|i| ([0, i + 1, i + 2], Some(i + 1))).collect();
+        let batched = batch_triangles(triangles.clone(), 12, 8);
+        assert_eq!(batched.batches.len(), 2);
+        let expected: Vec<(usize, u32)> = triangles.iter()
+            .flat_map(|&(indices, texture)| indices.iter().map(move |&index| (index as usize, texture.unwrap())))
+            .collect();
+        assert_eq!(sampled(&batched, 12), expected);
+    }
+
+    #[test]
+    fn untextured_vertices() {
+        let triangles = vec![([0, 1, 2], None), ([0, 2, 3], Some(4)), ([0, 1, 3], None)];
+        let batched = batch_triangles(triangles, 4, 8);
+        assert!(batched.copies.is_empty());
+        assert_eq!(batched.indices, vec![0, 1, 2, 0, 2, 3, 0, 1, 3]);
+        assert_eq!(batched.batches[0].textures, vec![4]);
+    }
+}
@@ -0,0 +1,500 @@
+extern crate color_quant;
+extern crate deflate;
+extern crate futures;
+extern crate gif;
+
+use error::QuicksilverError;
+use ffi::gl;
+use futures::{Async, Future, Poll};
+use graphics::Pixels;
+use graphics::pixels::flip_rows;
+use graphics::surface::current_framebuffer;
+use self::color_quant::NeuQuant;
+use self::gif::SetParameter;
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufWriter, Error as IOError, ErrorKind, Result as IOResult, Write},
+    path::{Path, PathBuf},
+    ptr::{null, null_mut},
+    slice,
+    sync::mpsc::{channel, Receiver, Sender, TryRecvError},
+    thread
+};
+
+// The number of frames that can be on their way back from the GPU at once
+const PIXEL_BUFFERS: usize = 3;
+
+/// The animated image format a capture is saved in
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum CaptureFormat {
+    /// An animated GIF, which has a palette of at most 256 colors per frame
+    Gif,
+    /// An animated PNG, which keeps every color but makes larger files
+    Apng
+}
+
+#[derive(Clone, Debug)]
+/// The settings for capturing what a Window presents into an animated image
+///
+/// Frames are copied into GPU buffers when the window presents them, and only read back a couple
+/// of frames later, once the copy has finished, so capturing doesn't wait on the GPU. They are
+/// then flipped, scaled, quantized and encoded on a separate thread.
+///
+/// Every frame is saved at the size of the first one, so if the window is resized during a
+/// capture, later frames are stretched to fit.
+pub struct CaptureSettings {
+    path: PathBuf,
+    format: CaptureFormat,
+    duration: f64,
+    frame_skip: u32,
+    scale: f32,
+    palette_size: usize,
+    quantize_speed: i32
+}
+
+impl CaptureSettings {
+    /// Capture a number of milliseconds of frames to a file
+    ///
+    /// Files ending in `.png` or `.apng` are saved as APNG, and anything else as a GIF.
+    pub fn new<P: AsRef<Path>>(path: P, duration: f64) -> CaptureSettings {
+        let path = PathBuf::from(path.as_ref());
+        let format = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("png") | Some("apng") => CaptureFormat::Apng,
+            _ => CaptureFormat::Gif
+        };
+        CaptureSettings {
+            path,
+            format,
+            duration,
+            frame_skip: 0,
+            scale: 1.0,
+            palette_size: 256,
+            quantize_speed: 10
+        }
+    }
+
+    /// Set the format to save in, instead of the one from the file extension
+    pub fn with_format(self, format: CaptureFormat) -> CaptureSettings {
+        CaptureSettings { format, ..self }
+    }
+
+    /// Set how many presented frames to skip after each captured frame (defaults to 0)
+    pub fn with_frame_skip(self, frame_skip: u32) -> CaptureSettings {
+        CaptureSettings { frame_skip, ..self }
+    }
+
+    /// Set how much to scale the frames down by, from 0 to 1 (defaults to 1)
+    pub fn with_scale(self, scale: f32) -> CaptureSettings {
+        assert!(scale > 0.0 && scale <= 1.0, "Captures can only be scaled down");
+        CaptureSettings { scale, ..self }
+    }
+
+    /// Set the number of colors in each GIF frame's palette, from 2 to 256 (defaults to 256)
+    pub fn with_palette_size(self, palette_size: usize) -> CaptureSettings {
+        assert!(palette_size >= 2 && palette_size <= 256, "GIF palettes have between 2 and 256 colors");
+        CaptureSettings { palette_size, ..self }
+    }
+
+    /// Set how quickly GIF palettes are chosen, from 1 for the best colors to 30 for the fastest
+    /// (defaults to 10)
+    pub fn with_quantize_speed(self, quantize_speed: i32) -> CaptureSettings {
+        CaptureSettings { quantize_speed: quantize_speed.max(1).min(30), ..self }
+    }
+}
+
+/// A Future for a capture, which is ready once the file is written
+///
+/// The capture ends after its duration, or when `Window::stop_capture` is called, and is then
+/// finished on its own thread. Polling it reports any error that happened while saving.
+pub struct Capture(Receiver<IOResult<()>>);
+
+impl Future for Capture {
+    type Item = ();
+    type Error = QuicksilverError;
+
+    fn poll(&mut self) -> Poll<(), QuicksilverError> {
+        match self.0.try_recv() {
+            Ok(result) => Ok(Async::Ready(result?)),
+            Err(TryRecvError::Empty) => Ok(Async::NotReady),
+            Err(TryRecvError::Disconnected) => Err(IOError::new(ErrorKind::Other, "The capture thread stopped").into())
+        }
+    }
+}
+
+// The Window's end of a running capture, which reads presented frames back from the GPU and sends
+// them to the encoding thread
+pub(crate) struct FrameCapture {
+    sender: Sender<(Pixels, f64)>,
+    frame_skip: u32,
+    skipped: u32,
+    end: f64,
+    // Pixel pack buffers that frames are copied into, used in turn, along with the size and time of
+    // the frame each is holding
+    buffers: Vec<u32>,
+    pending: [Option<(u32, u32, f64)>; PIXEL_BUFFERS],
+    next: usize
+}
+
+impl FrameCapture {
+    pub fn start(settings: CaptureSettings, now: f64) -> (FrameCapture, Capture) {
+        let (sender, frames) = channel();
+        let (result_sender, result) = channel();
+        let capture = FrameCapture {
+            sender,
+            // The first frame is always captured
+            frame_skip: settings.frame_skip,
+            skipped: settings.frame_skip,
+            end: now + settings.duration,
+            buffers: Vec::new(),
+            pending: [None; PIXEL_BUFFERS],
+            next: 0
+        };
+        thread::spawn(move || {
+            // The Capture may have been dropped, in which case nobody wants the result
+            let _ = result_sender.send(encode(&settings, frames));
+        });
+        (capture, Capture(result))
+    }
+
+    // Check if the capture has run for its whole duration
+    pub fn is_over(&self, now: f64) -> bool {
+        now >= self.end
+    }
+
+    // Check if the next presented frame should be captured, counting it if not
+    pub fn wants_frame(&mut self) -> bool {
+        if self.skipped >= self.frame_skip {
+            self.skipped = 0;
+            true
+        } else {
+            self.skipped += 1;
+            false
+        }
+    }
+
+    // Start copying an area of the window into the next buffer, and send on the frame that buffer
+    // held before, which was copied a couple of frames ago and so is ready without waiting
+    pub fn read(&mut self, x: i32, y: i32, width: u32, height: u32, now: f64) {
+        if gl::is_headless() {
+            self.send(Pixels::new(width, height, vec![0; (width * height * 4) as usize]), now);
+            return;
+        }
+        if self.buffers.is_empty() {
+            self.buffers = (0..PIXEL_BUFFERS).map(|_| unsafe { gl::GenBuffer() }).collect();
+        }
+        let index = self.next;
+        self.next = (index + 1) % PIXEL_BUFFERS;
+        self.finish_read(index);
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.buffers[index]);
+            gl::BufferData(gl::PIXEL_PACK_BUFFER, (width * height * 4) as isize, null(), gl::STREAM_READ);
+            // With a pack buffer bound, the pixels go into the buffer and the pointer is an offset
+            gl::ReadPixels(x, y, width as i32, height as i32, gl::RGBA, gl::UNSIGNED_BYTE, null_mut());
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, current_framebuffer());
+        }
+        self.pending[index] = Some((width, height, now));
+    }
+
+    // Map a buffer's frame, if it has one, and send a copy of it to be encoded
+    fn finish_read(&mut self, index: usize) {
+        if let Some((width, height, time)) = self.pending[index].take() {
+            let length = (width * height * 4) as usize;
+            let data = unsafe {
+                gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.buffers[index]);
+                let mapped = gl::MapBufferRange(gl::PIXEL_PACK_BUFFER, 0, length as isize, gl::MAP_READ_BIT) as *const u8;
+                let data = if mapped.is_null() { vec![0; length] } else { slice::from_raw_parts(mapped, length).to_vec() };
+                gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+                gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+                data
+            };
+            // The window's rows are from the bottom up, and are flipped on the encoding thread
+            self.send(Pixels::new(width, height, data), time);
+        }
+    }
+
+    fn send(&self, pixels: Pixels, now: f64) {
+        // If the thread failed it has already reported why
+        let _ = self.sender.send((pixels, now));
+    }
+}
+
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        if self.buffers.is_empty() {
+            return;
+        }
+        // Send the frames still in the buffers, oldest first
+        for offset in 0..PIXEL_BUFFERS {
+            self.finish_read((self.next + offset) % PIXEL_BUFFERS);
+        }
+        for &buffer in self.buffers.iter() {
+            unsafe { gl::DeleteBuffer(buffer) };
+        }
+    }
+}
+
+// The file a capture is being written to
+enum Encoder {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Apng(Apng)
+}
+
+impl Encoder {
+    fn new(settings: &CaptureSettings, width: u32, height: u32) -> IOResult<Encoder> {
+        let file = BufWriter::new(File::create(&settings.path)?);
+        Ok(match settings.format {
+            CaptureFormat::Gif => {
+                let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &[])?;
+                encoder.set(gif::Repeat::Infinite)?;
+                Encoder::Gif(encoder)
+            }
+            CaptureFormat::Apng => Encoder::Apng(Apng { file, width, height, frames: Vec::new() })
+        })
+    }
+
+    fn add(&mut self, settings: &CaptureSettings, pixels: &Pixels, delay: f64) -> IOResult<()> {
+        match self {
+            &mut Encoder::Gif(ref mut encoder) => {
+                let quantizer = NeuQuant::new(settings.quantize_speed, settings.palette_size, pixels.bytes());
+                let mut frame = gif::Frame::default();
+                frame.width = pixels.width() as u16;
+                frame.height = pixels.height() as u16;
+                frame.buffer = Cow::Owned(pixels.bytes().chunks(4).map(|pixel| quantizer.index_of(pixel) as u8).collect());
+                frame.palette = Some(quantizer.color_map_rgb());
+                // GIF delays are in hundredths of a second
+                frame.delay = (delay / 10.0).round().max(1.0).min(65535.0) as u16;
+                encoder.write_frame(&frame)
+            }
+            &mut Encoder::Apng(ref mut apng) => {
+                apng.add(pixels, delay);
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self) -> IOResult<()> {
+        match self {
+            Encoder::Gif(encoder) => {
+                // The GIF trailer is written when the encoder is dropped
+                drop(encoder);
+                Ok(())
+            }
+            Encoder::Apng(apng) => apng.finish()
+        }
+    }
+}
+
+// Encode the frames sent from the Window until it stops sending them, showing each one until the
+// next was presented
+//
+// The frames arrive with their rows from the bottom up, and are all resized to the size of the first.
+fn encode(settings: &CaptureSettings, frames: Receiver<(Pixels, f64)>) -> IOResult<()> {
+    let mut encoder: Option<Encoder> = None;
+    let mut size = (0, 0);
+    let mut pending: Option<(Pixels, f64)> = None;
+    let mut delay = 100.0;
+    for (pixels, time) in frames.iter() {
+        let (width, height) = (pixels.width(), pixels.height());
+        let mut data = pixels.into_bytes();
+        flip_rows(&mut data, width as usize * 4);
+        let pixels = Pixels::new(width, height, data);
+        if encoder.is_none() {
+            size = scaled_size(&pixels, settings.scale);
+            encoder = Some(Encoder::new(settings, size.0, size.1)?);
+        }
+        let pixels = resample(&pixels, size.0, size.1);
+        if let Some((previous, previous_time)) = pending.take() {
+            delay = time - previous_time;
+            encoder.as_mut().unwrap().add(settings, &previous, delay)?;
+        }
+        pending = Some((pixels, time));
+    }
+    match encoder {
+        Some(mut encoder) => {
+            // The last frame is shown for as long as the one before it
+            if let Some((last, _)) = pending {
+                encoder.add(settings, &last, delay)?;
+            }
+            encoder.finish()
+        }
+        None => Err(IOError::new(ErrorKind::Other, "No frames were captured"))
+    }
+}
+
+// An animated PNG, which has to know how many frames it has before any of them are written
+struct Apng {
+    file: BufWriter<File>,
+    width: u32,
+    height: u32,
+    frames: Vec<(Vec<u8>, u16)>
+}
+
+impl Apng {
+    fn add(&mut self, pixels: &Pixels, delay: f64) {
+        // Every row starts with its filter type, which is none
+        let row = pixels.width() as usize * 4;
+        let mut data = Vec::with_capacity((row + 1) * pixels.height() as usize);
+        for line in pixels.bytes().chunks(row) {
+            data.push(0);
+            data.extend_from_slice(line);
+        }
+        let delay = delay.round().max(1.0).min(65535.0) as u16;
+        self.frames.push((deflate::deflate_bytes_zlib(&data), delay));
+    }
+
+    fn finish(mut self) -> IOResult<()> {
+        self.file.write_all(&[137, 80, 78, 71, 13, 10, 26, 10])?;
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&u32_bytes(self.width));
+        header.extend_from_slice(&u32_bytes(self.height));
+        // 8 bits per channel of RGBA, with the standard compression and filtering, not interlaced
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+        write_chunk(&mut self.file, b"IHDR", &header)?;
+        let mut animation = u32_bytes(self.frames.len() as u32).to_vec();
+        // Loop forever
+        animation.extend_from_slice(&u32_bytes(0));
+        write_chunk(&mut self.file, b"acTL", &animation)?;
+        let mut sequence = 0;
+        for (index, &(ref data, delay)) in self.frames.iter().enumerate() {
+            let mut control = u32_bytes(sequence).to_vec();
+            for &value in [self.width, self.height, 0, 0].iter() {
+                control.extend_from_slice(&u32_bytes(value));
+            }
+            // The delay is a fraction of a second, and the frame replaces the one before it
+            control.extend_from_slice(&[(delay >> 8) as u8, delay as u8, (1000 >> 8) as u8, 1000u16 as u8, 0, 0]);
+            write_chunk(&mut self.file, b"fcTL", &control)?;
+            sequence += 1;
+            if index == 0 {
+                write_chunk(&mut self.file, b"IDAT", data)?;
+            } else {
+                let mut frame_data = u32_bytes(sequence).to_vec();
+                frame_data.extend_from_slice(data);
+                write_chunk(&mut self.file, b"fdAT", &frame_data)?;
+                sequence += 1;
+            }
+        }
+        write_chunk(&mut self.file, b"IEND", &[])?;
+        self.file.flush()
+    }
+}
+
+fn u32_bytes(value: u32) -> [u8; 4] {
+    [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+// Write a PNG chunk, which is its length, type, data and then a checksum of the type and data
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> IOResult<()> {
+    writer.write_all(&u32_bytes(data.len() as u32))?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    writer.write_all(&u32_bytes(crc32(kind.iter().chain(data.iter()))))
+}
+
+// The CRC-32 checksum PNG uses
+fn crc32<'a, I: Iterator<Item = &'a u8>>(bytes: I) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+// The size of pixels shrunk by a scale
+fn scaled_size(pixels: &Pixels, scale: f32) -> (u32, u32) {
+    let scale = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+    (scale(pixels.width()), scale(pixels.height()))
+}
+
+// Resize the pixels, averaging the block of pixels each new pixel covers when shrinking and
+// repeating pixels when growing
+fn resample(pixels: &Pixels, new_width: u32, new_height: u32) -> Pixels {
+    let (width, height) = (pixels.width(), pixels.height());
+    if (width, height) == (new_width, new_height) {
+        return pixels.clone();
+    }
+    // The source pixels from the start of one new pixel to the start of the next
+    let span = |index: u32, size: u32, new_size: u32| {
+        let start = index * size / new_size;
+        (start, ((index + 1) * size / new_size).max(start + 1))
+    };
+    let mut data = Vec::with_capacity((new_width * new_height * 4) as usize);
+    for y in 0..new_height {
+        let (top, bottom) = span(y, height, new_height);
+        for x in 0..new_width {
+            let (left, right) = span(x, width, new_width);
+            let mut sum = [0u32; 4];
+            for source_y in top..bottom {
+                let row = (source_y * width) as usize * 4;
+                for channel in row + left as usize * 4..row + right as usize * 4 {
+                    sum[channel % 4] += pixels.bytes()[channel] as u32;
+                }
+            }
+            let count = (right - left) * (bottom - top);
+            data.extend(sum.iter().map(|&total| ((total + count / 2) / count) as u8));
+        }
+    }
+    Pixels::new(new_width, new_height, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(b"123456789".iter()), 0xCBF4_3926);
+        assert_eq!(crc32(b"IEND".iter()), 0xAE42_6082);
+    }
+
+    #[test]
+    fn halve() {
+        let pixels = Pixels::new(2, 2, vec![
+            0, 0, 0, 255,    100, 0, 0, 255,
+            0, 200, 0, 255,  0, 0, 40, 255
+        ]);
+        assert_eq!(scaled_size(&pixels, 0.5), (1, 1));
+        assert_eq!(resample(&pixels, 1, 1).bytes(), &[25, 50, 10, 255]);
+        assert_eq!(resample(&pixels, 2, 2), pixels);
+        assert_eq!(&resample(&pixels, 4, 2).bytes()[..8], &[0, 0, 0, 255, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn resized_window() {
+        let path = ::std::env::temp_dir().join("quicksilver-resized-capture.png");
+        let (sender, frames) = channel();
+        sender.send((Pixels::new(2, 2, vec![255; 16]), 0.0)).unwrap();
+        sender.send((Pixels::new(4, 3, vec![255; 48]), 20.0)).unwrap();
+        drop(sender);
+        encode(&CaptureSettings::new(&path, 100.0), frames).unwrap();
+        let file = ::std::fs::read(&path).unwrap();
+        // Every frame control chunk has the size of the image from the header
+        let mut position = 8;
+        let mut sizes = Vec::new();
+        while position < file.len() {
+            let length = file[position..position + 4].iter().fold(0, |length, &byte| length << 8 | byte as usize);
+            let kind = &file[position + 4..position + 8];
+            let data = &file[position + 8..position + 8 + length];
+            if kind == b"IHDR" || kind == b"fcTL" {
+                let offset = if kind == b"fcTL" { 4 } else { 0 };
+                sizes.push((data[offset + 3], data[offset + 7]));
+            }
+            position += length + 12;
+        }
+        assert_eq!(sizes, vec![(2, 2), (2, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn frame_skip() {
+        let (mut capture, _) = FrameCapture::start(CaptureSettings::new("skip.gif", 100.0).with_frame_skip(2), 0.0);
+        let wanted: Vec<bool> = (0..6).map(|_| capture.wants_frame()).collect();
+        assert_eq!(wanted, vec![true, false, false, true, false, false]);
+        assert!(!capture.is_over(50.0));
+        assert!(capture.is_over(100.0));
+    }
+}
@@ -30,6 +30,16 @@ impl Color {
     pub fn with_alpha(self, a: f32) -> Color {
         Color { a, ..self }
     }
+
+    ///Multiply each component of the color by the matching component of another
+    pub fn multiply(self, other: Color) -> Color {
+        Color {
+            r: self.r * other.r,
+            g: self.g * other.g,
+            b: self.b * other.b,
+            a: self.a * other.a
+        }
+    }
 }
 
 #[allow(missing_docs)]
@@ -1,6 +1,6 @@
 use geom::{Circle, Positioned, Rectangle, Scalar, Shape, Transform, Vector};
-use graphics::{Color, GpuTriangle, Image, Vertex, Window};
-use std::iter;
+use graphics::{Color, GpuTriangle, Image, Stroke, Vertex, Window, WrapMode};
+use graphics::tessellation::{arc_segments, circle_segments, ellipse_points, fan, stroke, triangulate, Geometry};
 
 /// Some object that can be drawn to the screen
 pub trait Drawable {
@@ -12,7 +12,46 @@ pub trait Drawable {
 enum DrawPayload {
     Image(Image),
     Rectangle(Vector),
-    Circle(f32),
+    Ellipse(Vector, Option<Stroke>),
+    Arc(Vector, f32, f32, Stroke),
+    Sector(Vector, f32, f32),
+    RoundedRectangle(Vector, f32, Option<Stroke>),
+    NineSlice(Image, Vector, Insets, bool),
+    Tiled(Image, Vector, Vector, Vector),
+    Polyline(Vec<Vector>, Stroke),
+    Polygon(Vec<Vector>),
+    Outline(Vec<Vector>, Stroke)
+}
+
+/// The widths of the borders around the edges of a rectangle
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Insets {
+    /// The width of the left border
+    pub left: f32,
+    /// The height of the top border
+    pub top: f32,
+    /// The width of the right border
+    pub right: f32,
+    /// The height of the bottom border
+    pub bottom: f32
+}
+
+impl Insets {
+    /// Create insets with a different width for each border
+    pub fn new<T: Scalar>(left: T, top: T, right: T, bottom: T) -> Insets {
+        Insets {
+            left: left.float(),
+            top: top.float(),
+            right: right.float(),
+            bottom: bottom.float()
+        }
+    }
+
+    /// Create insets with the same width for every border
+    pub fn uniform<T: Scalar>(width: T) -> Insets {
+        let width = width.float();
+        Insets { left: width, top: width, right: width, bottom: width }
+    }
 }
 
 /// A single drawable item, with a transform, a blend color, and a depth
@@ -22,21 +61,56 @@ pub struct Draw {
     position: Vector,
     color: Color,
     transform: Transform,
-    z: f32
+    z: f32,
+    segments: Option<u32>
 }
 
 impl Draw {
-    /// Create a sprite with an image
-    pub fn image(image: &Image, position: Vector) -> Draw {
+    fn new(item: DrawPayload, position: Vector) -> Draw {
         Draw {
-            item: DrawPayload::Image(image.clone()),
+            item,
             position,
             color: Color::white(),
             transform: Transform::identity(),
-            z: 0.0
+            z: 0.0,
+            segments: None
         }
     }
 
+    /// Create a sprite with an image
+    pub fn image(image: &Image, position: Vector) -> Draw {
+        Draw::new(DrawPayload::Image(image.clone()), position)
+    }
+
+    /// Create a sprite that stretches an image over an area without stretching its corners
+    ///
+    /// The insets mark the borders of the image in pixels: the corners are drawn at their
+    /// original size, the edges stretch along their length, and the center stretches to fill the
+    /// rest. If the area is too small to fit the borders, they are scaled down to fit.
+    pub fn nine_slice(image: &Image, area: Rectangle, insets: Insets) -> Draw {
+        Draw::new(DrawPayload::NineSlice(image.clone(), area.size(), insets, false), area.center())
+    }
+
+    /// Create a sprite like `nine_slice`, but with the edges and center repeated instead of stretched
+    ///
+    /// The repeats are stretched when more than 256 of them would fit along a side.
+    pub fn nine_slice_tiled(image: &Image, area: Rectangle, insets: Insets) -> Draw {
+        Draw::new(DrawPayload::NineSlice(image.clone(), area.size(), insets, true), area.center())
+    }
+
+    /// Create a sprite that fills an area with copies of an image
+    ///
+    /// The scale sets the size of each copy relative to the image, and the offset shifts the
+    /// pattern by a fraction of a copy, which scrolls it when changed over time. Images with the
+    /// `MirroredRepeat` wrap mode flip every other copy. Subimages tile correctly too, but they
+    /// take a quad for each copy, while a whole texture with a repeating wrap mode takes one quad.
+    /// To keep the quad count bounded, subimages are scaled up so there are no more than 256
+    /// copies along each side. Both parts of the scale must be positive.
+    pub fn tiled(image: &Image, area: Rectangle, scale: Vector, offset: Vector) -> Draw {
+        assert!(scale.x > 0.0 && scale.y > 0.0, "Tiles must have a positive scale, not {:?}", scale);
+        Draw::new(DrawPayload::Tiled(image.clone(), area.size(), scale, offset), area.center())
+    }
+
     /// Create a sprite from a given shape
     pub fn shape(shape: Shape) -> Draw {
         match shape {
@@ -54,24 +128,98 @@ impl Draw {
 
     /// Create a sprite with a rectangle
     pub fn rectangle(rectangle: Rectangle) -> Draw {
-        Draw {
-            item: DrawPayload::Rectangle(rectangle.size()),
-            position: rectangle.center(),
-            color: Color::white(),
-            transform: Transform::identity(),
-            z: 0.0
-        }
+        Draw::new(DrawPayload::Rectangle(rectangle.size()), rectangle.center())
     }
 
     /// Create a sprite with a circle
     pub fn circle(circle: Circle) -> Draw {
-        Draw {
-            item: DrawPayload::Circle(circle.radius),
-            position: circle.center(),
-            color: Color::white(),
-            transform: Transform::identity(),
-            z: 0.0
-        }
+        Draw::ellipse(circle.center(), Vector::one() * circle.radius)
+    }
+
+    /// Create a sprite with an ellipse, from its center and its radius along each axis
+    pub fn ellipse(center: Vector, radii: Vector) -> Draw {
+        Draw::new(DrawPayload::Ellipse(radii, None), center)
+    }
+
+    /// Create a sprite with an arc along the edge of a circle
+    ///
+    /// The angles are in degrees, measured like `Vector::from_angle`, and the arc goes from the
+    /// start angle to the end angle
+    pub fn arc(circle: Circle, start_angle: f32, end_angle: f32, stroke: Stroke) -> Draw {
+        Draw::new(DrawPayload::Arc(Vector::one() * circle.radius, start_angle, end_angle - start_angle, stroke), circle.center())
+    }
+
+    /// Create a sprite with a filled sector (pie slice) of a circle
+    ///
+    /// The angles are in degrees, measured like `Vector::from_angle`
+    pub fn sector(circle: Circle, start_angle: f32, end_angle: f32) -> Draw {
+        Draw::new(DrawPayload::Sector(Vector::one() * circle.radius, start_angle, end_angle - start_angle), circle.center())
+    }
+
+    /// Create a sprite with a rectangle whose corners are rounded off with a radius
+    ///
+    /// The radius is limited to half of the rectangle's smaller side
+    pub fn rounded_rectangle(rectangle: Rectangle, radius: f32) -> Draw {
+        Draw::new(DrawPayload::RoundedRectangle(rectangle.size(), radius, None), rectangle.center())
+    }
+
+    /// Create a sprite with a straight line between two points
+    pub fn line(start: Vector, end: Vector, stroke: Stroke) -> Draw {
+        Draw::polyline(&[start, end], stroke)
+    }
+
+    /// Create a sprite with a line through a list of points
+    ///
+    /// The position of the sprite is the center of the points' bounding box
+    pub fn polyline(points: &[Vector], stroke: Stroke) -> Draw {
+        let (position, points) = center_points(points);
+        Draw::new(DrawPayload::Polyline(points, stroke), position)
+    }
+
+    /// Create a sprite with a filled triangle
+    pub fn triangle(a: Vector, b: Vector, c: Vector) -> Draw {
+        Draw::polygon(&[a, b, c])
+    }
+
+    /// Create a sprite with a filled polygon
+    ///
+    /// The polygon can be concave, but its edges shouldn't cross each other. The position of the
+    /// sprite is the center of the points' bounding box
+    pub fn polygon(points: &[Vector]) -> Draw {
+        let (position, points) = center_points(points);
+        Draw::new(DrawPayload::Polygon(points), position)
+    }
+
+    /// Create a sprite with the outline of a rectangle
+    pub fn rectangle_outline(rectangle: Rectangle, stroke: Stroke) -> Draw {
+        let half = rectangle.size() / 2;
+        let corners = vec![
+            -half, Vector::new(half.x, -half.y), half, Vector::new(-half.x, half.y)
+        ];
+        Draw::new(DrawPayload::Outline(corners, stroke), rectangle.center())
+    }
+
+    /// Create a sprite with the outline of a circle
+    pub fn circle_outline(circle: Circle, stroke: Stroke) -> Draw {
+        Draw::ellipse_outline(circle.center(), Vector::one() * circle.radius, stroke)
+    }
+
+    /// Create a sprite with the outline of an ellipse
+    pub fn ellipse_outline(center: Vector, radii: Vector, stroke: Stroke) -> Draw {
+        Draw::new(DrawPayload::Ellipse(radii, Some(stroke)), center)
+    }
+
+    /// Create a sprite with the outline of a rectangle with rounded corners
+    pub fn rounded_rectangle_outline(rectangle: Rectangle, radius: f32, stroke: Stroke) -> Draw {
+        Draw::new(DrawPayload::RoundedRectangle(rectangle.size(), radius, Some(stroke)), rectangle.center())
+    }
+
+    /// Create a sprite with the outline of a polygon
+    ///
+    /// The position of the sprite is the center of the points' bounding box
+    pub fn polygon_outline(points: &[Vector], stroke: Stroke) -> Draw {
+        let (position, points) = center_points(points);
+        Draw::new(DrawPayload::Outline(points, stroke), position)
     }
 
     /// Change the position of a sprite
@@ -98,6 +246,17 @@ impl Draw {
         }
     }
 
+    /// Set the number of segments a full turn of a curved shape is made of
+    ///
+    /// By default the number of segments is chosen from how large the shape is on screen, so that
+    /// large circles stay smooth and small ones don't waste vertices
+    pub fn with_segments(self, segments: u32) -> Draw {
+        Draw {
+            segments: Some(segments),
+            ..self
+        }
+    }
+
     /// Change the depth of a sprite
     pub fn with_z<T: Scalar>(self, z: T) -> Draw {
         Draw {
@@ -105,25 +264,94 @@ impl Draw {
             ..self
         }
     }
+    // The number of segments to use for part of a curve with a radius, sweeping through an angle
+    fn segments(&self, window: &Window, radius: f32, sweep: f32) -> usize {
+        let full = match self.segments {
+            Some(segments) => segments as usize,
+            None => {
+                // Measure how much the transform and the view scale the shape up on the screen
+                let matrix = (window.unproject() * self.transform).0;
+                let scale = (matrix[0][0].hypot(matrix[1][0])).max(matrix[0][1].hypot(matrix[1][1]));
+                circle_segments(radius * scale)
+            }
+        };
+        arc_segments(full, sweep)
+    }
 
+    // Find the triangles of a line, with round caps and joins as smooth as a circle of its width
+    fn stroke(&self, window: &Window, points: &[Vector], closed: bool, style: Stroke) -> Geometry {
+        stroke(points, closed, style, self.segments(window, style.thickness() / 2.0, 360.0))
+    }
+
+    // Add untextured geometry that is positioned relative to the sprite's position
+    fn add_geometry(&self, window: &mut Window, geometry: Geometry) {
+        let transform = Transform::translate(self.position) * self.transform;
+        let vertices = geometry.points.into_iter().map(|point| Vertex {
+            pos: transform * point,
+            tex_pos: None,
+            col: self.color
+        });
+        let z = self.z;
+        let triangles = geometry.triangles.into_iter().map(|indices| GpuTriangle {
+            z,
+            indices,
+            image: None
+        });
+        window.add_vertices(vertices, triangles);
+    }
+
+    // Add the slices of an image centered on the position, through the transform
+    fn add_slices(&self, window: &mut Window, image: &Image, size: Vector,
+                  columns: &[(f32, f32, f32, f32)], rows: &[(f32, f32, f32, f32)]) {
+        let transform = Transform::translate(self.position) * self.transform * Transform::translate(-size / 2);
+        add_slices(window, image, transform, self.color, self.z, columns, rows);
+    }
+}
+
+// Add a grid of textured quads, made of the columns and rows of (target start, target length,
+// source start, source length) pieces that an image is split into
+pub(crate) fn add_slices(window: &mut Window, image: &Image, transform: Transform, color: Color, z: f32,
+                         columns: &[(f32, f32, f32, f32)], rows: &[(f32, f32, f32, f32)]) {
+    let mut vertices = Vec::with_capacity(columns.len() * rows.len() * 4);
+    let mut triangles = Vec::with_capacity(columns.len() * rows.len() * 2);
+    for row in rows.iter() {
+        for column in columns.iter() {
+            let offset = vertices.len() as u32;
+            for &corner in [Vector::zero(), Vector::x(), Vector::one(), Vector::y()].iter() {
+                let target = Vector::new(column.0 + column.1 * corner.x, row.0 + row.1 * corner.y);
+                let source = Vector::new(column.2 + column.3 * corner.x, row.2 + row.3 * corner.y);
+                vertices.push(Vertex {
+                    pos: transform * target,
+                    tex_pos: Some(image.tex_coord(source)),
+                    col: color
+                });
+            }
+            for indices in [[0, 1, 2], [2, 3, 0]].iter() {
+                triangles.push(GpuTriangle {
+                    z,
+                    indices: [offset + indices[0], offset + indices[1], offset + indices[2]],
+                    image: Some(image.clone())
+                });
+            }
+        }
+    }
+    window.add_vertices(vertices.into_iter(), triangles.into_iter());
 }
 
 impl Drawable for Draw {
     fn draw(&self, window: &mut Window) {
         match self.item {
             DrawPayload::Image(ref image) => {
-                let area = image.area().with_center(self.position);
-                let trans = Transform::translate(area.top_left() + area.size() / 2) 
+                // Center the untrimmed image on the position, and only draw the pixels it kept
+                let trimmed = image.trimmed_area();
+                let trans = Transform::translate(self.position) 
                     * self.transform
-                    * Transform::translate(-area.size() / 2)
-                    * Transform::scale(area.size());
-                let recip_size = image.source_size().recip();
-                let normalized_pos = image.area().top_left().times(recip_size);
-                let normalized_size = image.area().size().times(recip_size);
+                    * Transform::translate(trimmed.top_left() - image.original_size() / 2);
                 let get_vertex = |v: Vector| {
+                    let point = v.times(trimmed.size());
                     Vertex {
-                        pos: trans * v,
-                        tex_pos: Some(normalized_pos + v.times(normalized_size)),
+                        pos: trans * point,
+                        tex_pos: Some(image.tex_coord(point)),
                         col: self.color
                     }
                 };
@@ -180,29 +408,206 @@ impl Drawable for Draw {
                 ];
                 window.add_vertices(vertices.iter().cloned(), triangles.iter().cloned());
             }
-            DrawPayload::Circle(radius) => {
-                let transform = Transform::translate(self.position)
-                    * self.transform
-                    * Transform::translate(-self.position);
-                let mut points = [Vector::zero(); 24]; // 24 = arbitrarily chosen number of points in the circle
-                let rotation = Transform::rotate(360f32 / points.len() as f32);
-                let mut arrow = Vector::new(0f32, -radius);
-                for i in 0..points.len() {
-                    points[i] = arrow + self.position;
-                    arrow = rotation * arrow;
-                }
-                let vertices = points.iter().map(|point| Vertex {
-                    pos: transform * point.clone(),
-                    tex_pos: None,
-                    col: self.color
-                });
-                let indices = iter::repeat(self.z).take(points.len() - 1).enumerate().map(|(index, z)| GpuTriangle {
-                    z,
-                    indices: [0, index as u32, index as u32 + 1],
-                    image: None
-                });
-                window.add_vertices(vertices, indices);
+            DrawPayload::Ellipse(radii, outline) => {
+                let segments = self.segments(window, radii.x.max(radii.y), 360.0);
+                let points = ellipse_points(Vector::zero(), radii, 0.0, 360.0, segments);
+                let geometry = match outline {
+                    Some(stroke_style) => self.stroke(window, &points, true, stroke_style),
+                    None => fan(Vector::zero(), &points, true)
+                };
+                self.add_geometry(window, geometry);
+            }
+            DrawPayload::Arc(radii, start, sweep, stroke_style) => {
+                let segments = self.segments(window, radii.x.max(radii.y), sweep);
+                let points = ellipse_points(Vector::zero(), radii, start, sweep, segments);
+                let geometry = self.stroke(window, &points, sweep.abs() >= 360.0, stroke_style);
+                self.add_geometry(window, geometry);
+            }
+            DrawPayload::Sector(radii, start, sweep) => {
+                let segments = self.segments(window, radii.x.max(radii.y), sweep);
+                let points = ellipse_points(Vector::zero(), radii, start, sweep, segments);
+                self.add_geometry(window, fan(Vector::zero(), &points, sweep.abs() >= 360.0));
+            }
+            DrawPayload::RoundedRectangle(size, radius, outline) => {
+                let half = size / 2;
+                let radius = radius.max(0.0).min(half.x.min(half.y));
+                let segments = self.segments(window, radius, 90.0);
+                let inner = half - Vector::one() * radius;
+                let corners = [
+                    (Vector::new(-inner.x, -inner.y), 180.0),
+                    (Vector::new(inner.x, -inner.y), 270.0),
+                    (Vector::new(inner.x, inner.y), 0.0),
+                    (Vector::new(-inner.x, inner.y), 90.0)
+                ];
+                let points: Vec<Vector> = corners.iter()
+                    .flat_map(|&(center, start)| ellipse_points(center, Vector::one() * radius, start, 90.0, segments))
+                    .collect();
+                let geometry = match outline {
+                    Some(stroke_style) => self.stroke(window, &points, true, stroke_style),
+                    None => fan(Vector::zero(), &points, true)
+                };
+                self.add_geometry(window, geometry);
+            }
+            DrawPayload::NineSlice(ref image, size, insets, tiled) => {
+                let source = image.trimmed_area().size();
+                let columns = slice_axis(size.x, insets.left, insets.right, source.x, tiled);
+                let rows = slice_axis(size.y, insets.top, insets.bottom, source.y, tiled);
+                self.add_slices(window, image, size, &columns, &rows);
+            }
+            DrawPayload::Tiled(ref image, size, scale, offset) => {
+                let source = image.trimmed_area().size();
+                let (columns, rows) = if image.is_whole_texture() && image.wrap() != WrapMode::Clamp {
+                    // The GPU can repeat the texture by itself
+                    (vec![(0.0, size.x, offset.x * source.x, size.x / scale.x)],
+                     vec![(0.0, size.y, offset.y * source.y, size.y / scale.y)])
+                } else {
+                    let mirrored = image.wrap() == WrapMode::MirroredRepeat;
+                    (tile_axis(size.x, source.x, scale.x, offset.x, mirrored),
+                     tile_axis(size.y, source.y, scale.y, offset.y, mirrored))
+                };
+                self.add_slices(window, image, size, &columns, &rows);
+            }
+            DrawPayload::Polyline(ref points, stroke_style) => {
+                let geometry = self.stroke(window, points, false, stroke_style);
+                self.add_geometry(window, geometry);
+            }
+            DrawPayload::Outline(ref points, stroke_style) => {
+                let geometry = self.stroke(window, points, true, stroke_style);
+                self.add_geometry(window, geometry);
+            }
+            DrawPayload::Polygon(ref points) => {
+                let triangles = triangulate(points);
+                self.add_geometry(window, Geometry { points: points.clone(), triangles });
+            }
+        }
+    }
+}
+
+// The most repeats along one axis of a tiled draw, past which the repeats are stretched so a
+// tiny tile can't turn into millions of quads
+const MAX_TILES: f32 = 256.0;
+
+// Split one axis of a nine-slice into pieces of (target start, target length, source start, source length)
+//
+// The borders keep their size unless the target is too short for them, and the middle either
+// stretches or repeats, with the last repeat cut short to fit.
+fn slice_axis(target: f32, start: f32, end: f32, source: f32, tiled: bool) -> Vec<(f32, f32, f32, f32)> {
+    let scale = if start + end > target && start + end > 0.0 { target / (start + end) } else { 1.0 };
+    let middle_target = target - (start + end) * scale;
+    let middle_source = source - start - end;
+    let mut pieces = vec![(0.0, start * scale, 0.0, start)];
+    if middle_target > 0.0 && middle_source > 0.0 {
+        if tiled {
+            let tile = middle_source.max(middle_target / MAX_TILES);
+            let mut offset = 0.0;
+            while offset < middle_target {
+                let length = tile.min(middle_target - offset);
+                pieces.push((start * scale + offset, length, start, length * middle_source / tile));
+                offset += length;
+            }
+        } else {
+            pieces.push((start * scale, middle_target, start, middle_source));
+        }
+    }
+    pieces.push((target - end * scale, end * scale, source - end, end));
+    pieces.into_iter().filter(|piece| piece.1 > 0.0).collect()
+}
+
+// Split one axis of a tiled fill into pieces of (target start, target length, source start, source length)
+//
+// Each copy of the image is `source * scale` long, and the pattern is shifted back by the offset,
+// in copies. Mirrored copies have a negative source length, so they run backwards through the image.
+fn tile_axis(target: f32, source: f32, scale: f32, offset: f32, mirrored: bool) -> Vec<(f32, f32, f32, f32)> {
+    let mut pieces = Vec::new();
+    if target <= 0.0 || source * scale <= 0.0 {
+        return pieces;
+    }
+    let scale = scale.max(target / (source * MAX_TILES));
+    let length = source * scale;
+    let mut index = offset.floor() as i64;
+    let mut start = -(offset - offset.floor()) * length;
+    while start < target {
+        let from = start.max(0.0);
+        let to = (start + length).min(target);
+        let source_from = (from - start) / scale;
+        let source_length = (to - from) / scale;
+        if mirrored && index % 2 != 0 {
+            pieces.push((from, to - from, source - source_from, -source_length));
+        } else {
+            pieces.push((from, to - from, source_from, source_length));
+        }
+        start += length;
+        index += 1;
+    }
+    pieces
+}
+
+// Find the center of the points' bounding box, and the points relative to it
+fn center_points(points: &[Vector]) -> (Vector, Vec<Vector>) {
+    if points.is_empty() {
+        return (Vector::zero(), Vec::new());
+    }
+    let min = points.iter().fold(points[0], |min, point| Vector::new(min.x.min(point.x), min.y.min(point.y)));
+    let max = points.iter().fold(points[0], |max, point| Vector::new(max.x.max(point.x), max.y.max(point.y)));
+    let center = (min + max) / 2;
+    (center, points.iter().map(|&point| point - center).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stretched_slices() {
+        let pieces = slice_axis(100.0, 8.0, 4.0, 32.0, false);
+        assert_eq!(pieces, vec![(0.0, 8.0, 0.0, 8.0), (8.0, 88.0, 8.0, 20.0), (96.0, 4.0, 28.0, 4.0)]);
+        let squashed = slice_axis(6.0, 8.0, 4.0, 32.0, false);
+        assert_eq!(squashed, vec![(0.0, 4.0, 0.0, 8.0), (4.0, 2.0, 28.0, 4.0)]);
+    }
+
+    #[test]
+    fn tiled_slices() {
+        let pieces = slice_axis(50.0, 8.0, 2.0, 30.0, true);
+        assert_eq!(pieces, vec![(0.0, 8.0, 0.0, 8.0), (8.0, 20.0, 8.0, 20.0), (28.0, 20.0, 8.0, 20.0), (48.0, 2.0, 28.0, 2.0)]);
+        let cut_short = slice_axis(45.0, 8.0, 2.0, 30.0, true);
+        assert_eq!(cut_short[2], (28.0, 15.0, 8.0, 15.0));
+    }
+
+    #[test]
+    fn tiled_fill() {
+        let pieces = tile_axis(50.0, 10.0, 2.0, 0.25, false);
+        assert_eq!(pieces, vec![(0.0, 15.0, 2.5, 7.5), (15.0, 20.0, 0.0, 10.0), (35.0, 15.0, 0.0, 7.5)]);
+        let mirrored = tile_axis(40.0, 10.0, 2.0, 1.0, true);
+        assert_eq!(mirrored, vec![(0.0, 20.0, 10.0, -10.0), (20.0, 20.0, 0.0, 10.0)]);
+        assert!(tile_axis(40.0, 10.0, 0.0, 0.0, false).is_empty());
+    }
+
+    #[test]
+    #[cfg(not(target_arch="wasm32"))]
+    fn non_positive_tile_scale() {
+        use ffi::gl;
+        use graphics::PixelFormat;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        gl::set_headless(true);
+        let texture = Image::new_null(16, 16, PixelFormat::RGBA);
+        texture.set_wrap(WrapMode::Repeat);
+        let subimage = texture.subimage(Rectangle::new(0, 0, 8, 8));
+        let area = Rectangle::new(0, 0, 64, 64);
+        for scale in [Vector::new(0, 1), Vector::new(1, -1)].iter() {
+            for image in [&texture, &subimage].iter() {
+                let tiled = catch_unwind(AssertUnwindSafe(|| Draw::tiled(image, area, *scale, Vector::zero())));
+                assert!(tiled.is_err());
             }
         }
     }
+
+    #[test]
+    fn tile_limit() {
+        let pieces = tile_axis(1000.0, 10.0, 0.0001, 0.0, false);
+        assert_eq!(pieces.len(), MAX_TILES as usize);
+        assert_eq!(pieces[1], (1000.0 / MAX_TILES, 1000.0 / MAX_TILES, 0.0, 10.0));
+        let slices = slice_axis(102_402.0, 1.0, 1.0, 3.0, true);
+        assert_eq!(slices.len(), MAX_TILES as usize + 2);
+        assert_eq!(slices[1].3, 1.0);
+    }
 }
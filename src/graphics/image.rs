@@ -6,7 +6,9 @@ use error::QuicksilverError;
 use ffi::gl;
 use futures::{Async, Future, Poll};
 use geom::{Rectangle, Vector};
+use graphics::{ImageScaleStrategy, Pixels};
 use std::{
+    cell::Cell,
     error::Error,
     fmt,
     io::Error as IOError,
@@ -31,17 +33,77 @@ pub enum PixelFormat {
     BGRA = gl::BGRA as isize,
 }
 
+///The way an image is sampled outside of its texture, for example by a tiled fill
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum WrapMode {
+    /// The color at the nearest edge of the texture is used
+    Clamp = gl::CLAMP_TO_EDGE,
+    /// The texture repeats
+    Repeat = gl::REPEAT,
+    /// The texture repeats, flipping every other copy so the edges meet seamlessly
+    MirroredRepeat = gl::MIRRORED_REPEAT
+}
+
+// How an atlas packer turned an image when storing it in its source
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Rotation {
+    Upright,
+    CounterClockwise,
+    Clockwise
+}
+
+thread_local! {
+    // The scale strategy new textures start with, which is set by the Window
+    static DEFAULT_SCALE_STRATEGY: Cell<ImageScaleStrategy> = Cell::new(ImageScaleStrategy::Pixelate);
+}
+
+pub(crate) fn set_default_scale_strategy(strategy: ImageScaleStrategy) {
+    DEFAULT_SCALE_STRATEGY.with(|default| default.set(strategy));
+}
+
 #[derive(Debug)]
 struct ImageData {
     id: u32,
     width: u32,
     height: u32,
+    wrap: Cell<WrapMode>,
+    scale_strategy: Cell<ImageScaleStrategy>
+}
+
+impl ImageData {
+    fn new(id: u32, width: u32, height: u32) -> ImageData {
+        ImageData {
+            id,
+            width,
+            height,
+            wrap: Cell::new(WrapMode::Clamp),
+            scale_strategy: Cell::new(DEFAULT_SCALE_STRATEGY.with(|default| default.get()))
+        }
+    }
+
+    // Send the wrap mode and scale strategy to the texture
+    fn apply_parameters(&self) {
+        if self.id == 0 {
+            return;
+        }
+        let wrap = self.wrap.get() as i32;
+        let filter = self.scale_strategy.get() as i32;
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter);
+        }
+    }
 }
 
 impl Drop for ImageData {
     fn drop(&mut self) {
-        unsafe {
-            gl::DeleteTexture(self.id);
+        // Images created without a graphics context have no texture
+        if self.id != 0 && !gl::is_headless() {
+            unsafe { gl::DeleteTexture(self.id) };
         }
     }
 }
@@ -51,6 +113,9 @@ impl Drop for ImageData {
 pub struct Image {
     source: Rc<ImageData>,
     region: Rectangle,
+    rotation: Rotation,
+    offset: Vector,
+    original_size: Vector
 }
 
 impl Image {
@@ -58,7 +123,10 @@ impl Image {
         let region = Rectangle::new_sized(data.width, data.height);
         Image {
             source: Rc::new(data),
-            region
+            region,
+            rotation: Rotation::Upright,
+            offset: Vector::zero(),
+            original_size: region.size()
         }
     }
    
@@ -84,20 +152,27 @@ impl Image {
     }
 
     fn from_ptr(data: *const c_void, width: u32, height: u32, format: PixelFormat) -> Image {
+        if gl::is_headless() {
+            return Image::new(ImageData::new(0, width, height));
+        }
         unsafe {
             let id = gl::GenTexture();
             gl::BindTexture(gl::TEXTURE_2D, id);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
             gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, width as i32, 
                            height as i32, 0, format as u32, gl::UNSIGNED_BYTE, data);
             gl::GenerateMipmap(gl::TEXTURE_2D);
-            Image::new(ImageData { id, width, height })
+            let data = ImageData::new(id, width, height);
+            data.apply_parameters();
+            Image::new(data)
         }
     }
 
+    // An image with a made up texture id, to test batching without a graphics context
+    #[cfg(test)]
+    pub(crate) fn with_id(id: u32, width: u32, height: u32) -> Image {
+        Image::new(ImageData::new(id, width, height))
+    }
+
     pub(crate) fn new_null(width: u32, height: u32, format: PixelFormat) -> Image {
         use std::ptr::null;
         Image::from_ptr(null(), width, height, format)
@@ -124,12 +199,108 @@ impl Image {
         Vector::new(self.source_width(), self.source_height())
     }
 
+    // Find the normalized texture coordinate of a point in the subimage, in pixels from its top left
+    //
+    // Rotated images are stored on their side, so the point is turned to match
+    pub(crate) fn tex_coord(&self, point: Vector) -> Vector {
+        let point = match self.rotation {
+            Rotation::Upright => point,
+            Rotation::CounterClockwise => Vector::new(point.y, self.region.height - point.x),
+            Rotation::Clockwise => Vector::new(self.region.width - point.y, point.x)
+        };
+        (self.region.top_left() + point).times(self.source_size().recip())
+    }
+
+    ///Set how the image is sampled outside of its texture
+    ///
+    ///This changes the texture itself, so it applies to every subimage of the same source.
+    ///Subimages of a larger texture can't wrap on the GPU, but `Draw::tiled` repeats them anyway.
+    pub fn set_wrap(&self, wrap: WrapMode) {
+        self.source.wrap.set(wrap);
+        self.source.apply_parameters();
+    }
+
+    ///Get how the image is sampled outside of its texture (defaults to `WrapMode::Clamp`)
+    pub fn wrap(&self) -> WrapMode {
+        self.source.wrap.get()
+    }
+
+    ///Set how the image is filtered when it is drawn at a different scale
+    ///
+    ///This overrides the Window's scaling strategy, and like `set_wrap` it applies to every
+    ///subimage of the same source
+    pub fn set_scale_strategy(&self, strategy: ImageScaleStrategy) {
+        self.source.scale_strategy.set(strategy);
+        self.source.apply_parameters();
+    }
+
+    ///Get how the image is filtered when it is drawn at a different scale
+    pub fn scale_strategy(&self) -> ImageScaleStrategy {
+        self.source.scale_strategy.get()
+    }
+
+    // Check if the image covers its whole texture, so the GPU can wrap it
+    pub(crate) fn is_whole_texture(&self) -> bool {
+        !self.is_rotated() && self.region == Rectangle::new_sized(self.source.width, self.source.height)
+    }
+
+    ///Read the pixels of the image back from the GPU
+    ///
+    ///This reads the area the image is stored in, so a rotated image comes back on its side. Like
+    ///using a Surface's image, it shouldn't be done within `Surface::render_to`.
+    pub fn read_pixels(&self) -> Pixels {
+        let region = self.region;
+        let read = |framebuffer| Pixels::read(framebuffer, region.x as i32, region.y as i32,
+                                              region.width as u32, region.height as u32, false);
+        if gl::is_headless() {
+            return read(0);
+        }
+        unsafe {
+            let framebuffer = gl::GenFramebuffer();
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::FramebufferTexture(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, self.get_id(), 0);
+            let pixels = read(framebuffer);
+            gl::DeleteFramebuffer(framebuffer);
+            pixels
+        }
+    }
+
     ///The area of the source image this subimage takes up
+    ///
+    ///For a rotated image this is the area it was packed into, so its width and height are swapped
     pub fn area(&self) -> Rectangle {
         self.region
     }
 
+    ///If the image is stored in its source turned 90 degrees, as atlas packers do
+    ///
+    ///Drawing the image turns it back upright.
+    pub fn is_rotated(&self) -> bool {
+        self.rotation != Rotation::Upright
+    }
+
+    ///The size of the image before any transparent border was trimmed away
+    pub fn original_size(&self) -> Vector {
+        self.original_size
+    }
+
+    ///The upright area the stored pixels cover within the original, untrimmed image
+    ///
+    ///Drawing the image centers its original size on the position, and places these pixels here.
+    pub fn trimmed_area(&self) -> Rectangle {
+        let size = if self.is_rotated() { Vector::new(self.region.height, self.region.width) } else { self.region.size() };
+        Rectangle::newv(self.offset, size)
+    }
+
+    // Mark the image as rotated and trimmed, with the offset of its pixels from the top left of the original
+    pub(crate) fn with_packing(self, rotation: Rotation, offset: Vector, original_size: Vector) -> Image {
+        Image { rotation, offset, original_size, ..self }
+    }
+
     ///Find a subimage of a larger image
+    ///
+    ///The rectangle is relative to the area the image is stored in, so the subimage is never
+    ///rotated or trimmed
     pub fn subimage(&self, rect: Rectangle) -> Image {
         Image {
             source: self.source.clone(),
@@ -139,6 +310,9 @@ impl Image {
                 rect.width,
                 rect.height,
             ),
+            rotation: Rotation::Upright,
+            offset: Vector::zero(),
+            original_size: rect.size()
         }
     }
 }
@@ -167,11 +341,15 @@ impl Future for ImageLoader {
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         use ffi::wasm;
         Ok(match wasm::asset_status(self.id)? {
-            true => Async::Ready(Image::new(ImageData {
-                    id: unsafe { wasm::get_image_id(self.id) },
-                    width: unsafe { wasm::get_image_width(self.id) },
-                    height: unsafe { wasm::get_image_height(self.id) }
-                })),
+            true => Async::Ready({
+                let data = unsafe { ImageData::new(
+                    wasm::get_image_id(self.id),
+                    wasm::get_image_width(self.id),
+                    wasm::get_image_height(self.id)
+                ) };
+                data.apply_parameters();
+                Image::new(data)
+            }),
             false => Async::NotReady,
         })
     }
@@ -0,0 +1,248 @@
+use ffi::gl;
+use geom::Transform;
+use graphics::{Color, Drawable, GpuTriangle, Image, Vertex, Window};
+use graphics::backend::{batch_triangles, set_vertex_attributes, write_vertex, Batch, VERTEX_SIZE};
+use graphics::shader::MAX_BATCH_TEXTURES;
+use std::{
+    mem::size_of,
+    os::raw::c_void
+};
+
+/// A set of vertices and triangles that is uploaded to the GPU once and kept there
+///
+/// Drawing a Window's items sends all of their vertices to the GPU every frame, which is wasteful
+/// for geometry that rarely changes, like a tiled background. A Mesh keeps its data in its own
+/// GPU buffers, so it can be drawn many times with `Window::draw_mesh` for the cost of a draw call
+/// or two, each time with its own transform and tint.
+///
+/// Meshes are drawn as soon as `Window::draw_mesh` is called, on top of everything drawn before
+/// them and regardless of the z of their triangles, and always with the default shader.
+pub struct Mesh {
+    vertices: Vec<Vertex>,
+    triangles: Vec<GpuTriangle>,
+    units: Vec<f32>,
+    copies: Vec<usize>,
+    indices: Vec<u32>,
+    batches: Vec<Batch>,
+    vao: u32,
+    vbo: u32,
+    ebo: u32
+}
+
+impl Mesh {
+    /// Upload vertices and the triangles between them to the GPU
+    ///
+    /// The triangles are sorted by z and image once, here. Like with `Window::add_vertices`, the
+    /// indices of the triangles refer to the given vertices.
+    pub fn new(vertices: &[Vertex], triangles: &[GpuTriangle]) -> Mesh {
+        let mut triangles = triangles.to_vec();
+        triangles.sort();
+        assert!(triangles.iter().all(|triangle| triangle.indices.iter().all(|&index| (index as usize) < vertices.len())),
+            "Mesh triangles must only use indices of the mesh's vertices");
+        // The triangles keep their images alive, so the batches only need the textures' ids
+        let batched = batch_triangles(triangles.iter().map(|triangle| (triangle.indices, triangle.image.as_ref().map(Image::get_id))),
+                                      vertices.len(), MAX_BATCH_TEXTURES);
+        let mut mesh = Mesh {
+            vertices: vertices.to_vec(),
+            triangles,
+            units: batched.units,
+            copies: batched.copies,
+            indices: batched.indices,
+            batches: batched.batches,
+            vao: 0,
+            vbo: 0,
+            ebo: 0
+        };
+        if !gl::is_headless() {
+            let indices = &mesh.indices;
+            unsafe {
+                mesh.vao = gl::GenVertexArray();
+                gl::BindVertexArray(mesh.vao);
+                mesh.vbo = gl::GenBuffer();
+                gl::BindBuffer(gl::ARRAY_BUFFER, mesh.vbo);
+                let data = mesh.serialize(0, mesh.units.len());
+                gl::BufferData(gl::ARRAY_BUFFER, (data.len() * size_of::<f32>()) as isize,
+                               data.as_ptr() as *const c_void, gl::STATIC_DRAW);
+                set_vertex_attributes();
+                mesh.ebo = gl::GenBuffer();
+                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, mesh.ebo);
+                gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, (indices.len() * size_of::<u32>()) as isize,
+                               indices.as_ptr() as *const c_void, gl::STATIC_DRAW);
+            }
+        }
+        mesh
+    }
+
+    /// Replace some of the mesh's vertices, starting from the vertex at `offset`
+    ///
+    /// Only the replaced vertices are uploaded to the GPU. The triangles stay the same, so each
+    /// new vertex should agree with the old one on whether it uses an image.
+    pub fn update_vertices(&mut self, offset: usize, vertices: &[Vertex]) {
+        let end = offset + vertices.len();
+        assert!(end <= self.vertices.len(), "Mesh vertex updates must stay within the mesh");
+        self.vertices[offset..end].copy_from_slice(vertices);
+        if self.vbo != 0 {
+            for (start, end) in self.spans(offset, end) {
+                self.upload(start, end);
+            }
+        }
+    }
+
+    /// Replace a single vertex of the mesh
+    pub fn update_vertex(&mut self, index: usize, vertex: Vertex) {
+        self.update_vertices(index, &[vertex]);
+    }
+
+    /// The vertices of the mesh
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    /// The triangles of the mesh, in the order they are drawn
+    pub fn triangles(&self) -> &[GpuTriangle] {
+        &self.triangles
+    }
+
+    pub(crate) fn vao(&self) -> u32 {
+        self.vao
+    }
+
+    pub(crate) fn batches(&self) -> &[Batch] {
+        &self.batches
+    }
+
+    // Find the spans of the GPU buffer that hold the vertices from `offset` up to `end`
+    //
+    // Vertices shared between texture units have copies after the mesh's own vertices, which are
+    // covered by a second span.
+    fn spans(&self, offset: usize, end: usize) -> Vec<(usize, usize)> {
+        let mut spans = vec![(offset, end)];
+        let replaced = |source: &usize| *source >= offset && *source < end;
+        if let Some(first) = self.copies.iter().position(&replaced) {
+            let last = self.copies.iter().rposition(&replaced).unwrap_or(first);
+            let count = self.vertices.len();
+            spans.push((count + first, count + last + 1));
+        }
+        spans
+    }
+
+    // Write the data of the vertices in the GPU buffer from `start` up to `end`
+    fn serialize(&self, start: usize, end: usize) -> Vec<f32> {
+        let mut data = Vec::with_capacity((end - start) * VERTEX_SIZE);
+        for index in start..end {
+            let source = if index < self.vertices.len() { index } else { self.copies[index - self.vertices.len()] };
+            write_vertex(&mut data, &self.vertices[source], self.units[index]);
+        }
+        data
+    }
+
+    fn upload(&self, start: usize, end: usize) {
+        let data = self.serialize(start, end);
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferSubData(gl::ARRAY_BUFFER, (start * VERTEX_SIZE * size_of::<f32>()) as isize,
+                              (data.len() * size_of::<f32>()) as isize, data.as_ptr() as *const c_void);
+        }
+    }
+}
+
+impl Drawable for Mesh {
+    fn draw(&self, window: &mut Window) {
+        window.draw_mesh(self, Transform::identity(), Color::white());
+    }
+}
+
+impl Drop for Mesh {
+    fn drop(&mut self) {
+        // Meshes created without a graphics context have no buffers
+        if self.vao != 0 {
+            unsafe {
+                gl::DeleteBuffer(self.vbo);
+                gl::DeleteBuffer(self.ebo);
+                gl::DeleteVertexArray(self.vao);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch="wasm32")))]
+mod tests {
+    use super::*;
+    use geom::Vector;
+    use graphics::WindowBuilder;
+
+    fn vertex(x: f32, y: f32) -> Vertex {
+        Vertex { pos: Vector::new(x, y), tex_pos: None, col: Color::white() }
+    }
+
+    fn quad() -> Mesh {
+        let vertices = [vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(1.0, 1.0), vertex(0.0, 1.0)];
+        let triangles = [
+            GpuTriangle { z: 1.0, indices: [2, 3, 0], image: None },
+            GpuTriangle { z: 0.0, indices: [0, 1, 2], image: None }
+        ];
+        Mesh::new(&vertices, &triangles)
+    }
+
+    #[test]
+    fn partial_update() {
+        gl::set_headless(true);
+        let mut mesh = quad();
+        assert_eq!(mesh.triangles()[0].indices, [0, 1, 2]);
+        assert_eq!(mesh.batches().len(), 1);
+        assert_eq!(mesh.batches()[0].count, 6);
+        mesh.update_vertices(1, &[vertex(2.0, 0.0), vertex(2.0, 2.0)]);
+        assert_eq!(mesh.vertices()[0].pos, Vector::zero());
+        assert_eq!(mesh.vertices()[2].pos, Vector::new(2, 2));
+    }
+
+    #[test]
+    fn draw() {
+        let mut window = WindowBuilder::new("", 800, 600).build_headless();
+        let mesh = quad();
+        window.draw_mesh(&mesh, Transform::translate(Vector::new(10, 20)), Color::red());
+        window.draw_mesh(&mesh, Transform::identity(), Color::white());
+        assert_eq!(window.drawn_vertices.len(), 8);
+        assert_eq!(window.drawn_triangles.len(), 4);
+        assert_eq!(window.drawn_vertices[2].pos, Vector::new(11, 21));
+        assert_eq!(window.drawn_vertices[2].col, Color::red());
+        assert_eq!(window.drawn_triangles[3].indices, [6, 7, 4]);
+    }
+
+    #[test]
+    fn shared_vertex_textures() {
+        gl::set_headless(true);
+        // A quad whose halves have different textures, so the diagonal's corners need copies
+        let images = [Image::with_id(1, 4, 4), Image::with_id(2, 4, 4)];
+        let vertices = [vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(1.0, 1.0), vertex(0.0, 1.0)];
+        let triangles = [
+            GpuTriangle { z: 0.0, indices: [0, 1, 2], image: Some(images[0].clone()) },
+            GpuTriangle { z: 0.0, indices: [2, 3, 0], image: Some(images[1].clone()) }
+        ];
+        let mut mesh = Mesh::new(&vertices, &triangles);
+        assert_eq!(mesh.batches().len(), 1);
+        let mut copied = mesh.copies.clone();
+        copied.sort();
+        assert_eq!(copied, vec![0, 2]);
+        // Every corner is drawn from its own vertex, on the unit of its triangle's texture
+        for (position, &index) in mesh.indices.iter().enumerate() {
+            let triangle = &mesh.triangles()[position / 3];
+            let index = index as usize;
+            let source = if index < 4 { index } else { mesh.copies[index - 4] };
+            assert_eq!(source as u32, triangle.indices[position % 3]);
+            let texture = mesh.batches()[0].textures[mesh.units[index] as usize];
+            assert_eq!(Some(texture), triangle.image.as_ref().map(Image::get_id));
+        }
+        mesh.update_vertex(2, vertex(2.0, 3.0));
+        let copy = 4 + mesh.copies.iter().position(|&source| source == 2).unwrap();
+        assert_eq!(mesh.spans(2, 3), vec![(2, 3), (copy, copy + 1)]);
+        assert_eq!(&mesh.serialize(copy, copy + 1)[..2], &[2.0, 3.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn update_out_of_bounds() {
+        gl::set_headless(true);
+        quad().update_vertices(3, &[vertex(0.0, 0.0), vertex(0.0, 0.0)]);
+    }
+}
@@ -3,13 +3,21 @@
 
 mod animation;
 mod atlas;
+#[cfg(feature="atlas_json")] mod atlas_json;
 mod backend;
+#[cfg(all(feature="capture", not(target_arch="wasm32")))] mod capture;
 mod color;
 mod drawable;
 #[cfg(feature="fonts")] mod font;
 mod image;
+mod mesh;
+mod packer;
+mod pixels;
 mod resize;
+mod shader;
+mod stats;
 mod surface;
+mod tessellation;
 mod vertex;
 mod view;
 mod window;
@@ -19,13 +27,24 @@ pub use self::{
     atlas::{Atlas, AtlasError, AtlasItem, AtlasLoader},
     backend::BlendMode,
     color::Color,
-    drawable::{Draw, Drawable},
-    image::{Image, ImageError, ImageLoader, PixelFormat},
+    drawable::{Draw, Drawable, Insets},
+    image::{Image, ImageError, ImageLoader, PixelFormat, WrapMode},
+    mesh::Mesh,
+    packer::{AtlasPacker, pack_rectangles},
+    pixels::Pixels,
     resize::ResizeStrategy,
+    shader::{Shader, ShaderError, Uniform},
+    stats::FrameStats,
     surface::Surface,
+    tessellation::{LineCap, LineJoin, Stroke, StrokeAlignment},
     vertex::{Vertex, GpuTriangle},
     view::View,
     window::{ImageScaleStrategy, Window, WindowBuilder}
 };
+#[cfg(all(feature="capture", not(target_arch="wasm32")))] pub use self::capture::{Capture, CaptureFormat, CaptureSettings};
 #[cfg(feature="fonts")] pub use self::font::{Font, FontLoader};
-pub(crate) use self::backend::Backend;
+#[cfg(feature="fonts")] pub use self::stats::StatsOverlay;
+pub(crate) use self::{
+    backend::Backend,
+    stats::StatsCollector
+};
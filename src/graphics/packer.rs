@@ -0,0 +1,248 @@
+use geom::{Rectangle, Transform, Vector};
+use graphics::{Atlas, AtlasError, BlendMode, Color, Image, PixelFormat, Surface, Window};
+use graphics::atlas::{create, Region};
+use graphics::drawable::add_slices;
+use graphics::image::Rotation;
+
+/// A builder that packs loose images into the pages of an Atlas at runtime
+///
+/// Every image is its own texture until it is packed, so drawing many of them breaks up batching.
+/// Packing copies them onto a few large Surfaces, found with `Atlas::get` under the names they
+/// were added with, like the items of a loaded atlas.
+///
+/// Padding keeps empty pixels between the packed images, and extrusion repeats their edge pixels
+/// around them, which stops filtering from bleeding their neighbours into them when drawn scaled.
+pub struct AtlasPacker {
+    page_size: (u32, u32),
+    padding: u32,
+    extrusion: u32,
+    images: Vec<(String, i32, Image)>
+}
+
+impl AtlasPacker {
+    /// Create a packer that makes pages of a given size
+    pub fn new(page_width: u32, page_height: u32) -> AtlasPacker {
+        AtlasPacker {
+            page_size: (page_width, page_height),
+            padding: 2,
+            extrusion: 0,
+            images: Vec::new()
+        }
+    }
+
+    /// Set the number of empty pixels between packed images (defaults to 2)
+    pub fn with_padding(self, padding: u32) -> AtlasPacker {
+        AtlasPacker { padding, ..self }
+    }
+
+    /// Set how many times the edge pixels of each image are repeated around it (defaults to 0)
+    pub fn with_extrusion(self, extrusion: u32) -> AtlasPacker {
+        AtlasPacker { extrusion, ..self }
+    }
+
+    /// Add an image as a still frame
+    pub fn add_image(&mut self, name: &str, image: &Image) {
+        self.images.push((name.to_owned(), -1, image.clone()));
+    }
+
+    /// Add an image as a frame of an animation, ordered by its index
+    pub fn add_frame(&mut self, name: &str, index: u32, image: &Image) {
+        self.images.push((name.to_owned(), index as i32, image.clone()));
+    }
+
+    /// Add raw pixel data as a still frame
+    pub fn add_pixels(&mut self, name: &str, data: &[u8], width: u32, height: u32, format: PixelFormat) {
+        let image = Image::from_raw(data, width, height, format);
+        self.add_image(name, &image);
+    }
+
+    /// Copy the images onto as many pages as they need, and create an Atlas of them
+    ///
+    /// This draws to the pages with the Window, so it should be done outside of `State::draw`.
+    /// If an image is too large to fit on a page, its name is returned in an error.
+    pub fn pack(self, window: &mut Window) -> Result<Atlas, AtlasError> {
+        let (width, height) = self.page_size;
+        let border = 2 * self.extrusion;
+        // Every cell is followed by the padding, which may hang off the edge of the page
+        let sizes: Vec<(u32, u32)> = self.images.iter()
+            .map(|&(_, _, ref image)| {
+                let size = image.trimmed_area().size();
+                (size.x.round() as u32 + border + self.padding, size.y.round() as u32 + border + self.padding)
+            })
+            .collect();
+        let placements = pack_rectangles(&sizes, (width + self.padding, height + self.padding))
+            .map_err(|index| AtlasError::TooLarge(self.images[index].0.clone()))?;
+        let page_count = placements.iter().map(|&(page, _, _)| page + 1).max().unwrap_or(0);
+        let mut pages = Vec::with_capacity(page_count);
+        let mut regions = Vec::with_capacity(self.images.len());
+        for page in 0..page_count {
+            let surface = Surface::new(width, height);
+            surface.render_to(window, |window| {
+                window.clear(Color::black().with_alpha(0.0));
+                // The maximum of each color and nothing is the color itself, alpha included
+                window.set_blend_mode(BlendMode::Maximum);
+                for (&(ref name, index, ref image), &(_, x, y)) in self.images.iter().zip(placements.iter())
+                        .filter(|&(_, &(image_page, _, _))| image_page == page) {
+                    let position = Vector::new(x + self.extrusion, y + self.extrusion);
+                    add_extruded(window, image, position, self.extrusion as f32);
+                    let trimmed = image.trimmed_area();
+                    regions.push(Region {
+                        image: page,
+                        name: name.clone(),
+                        rotate: Rotation::Upright,
+                        region: Rectangle::newv(position, trimmed.size()),
+                        offset: trimmed.top_left(),
+                        original_size: image.original_size(),
+                        duration: None,
+                        index
+                    });
+                }
+                window.reset_blend_mode();
+            });
+            pages.push(surface.image().clone());
+        }
+        Ok(create((pages, regions)))
+    }
+}
+
+// Draw an image with its edge pixels stretched out around it, by splitting it into slices like
+// a nine-slice
+fn add_extruded(window: &mut Window, image: &Image, position: Vector, extrusion: f32) {
+    let size = image.trimmed_area().size();
+    let axis = |start: f32, length: f32| {
+        // The extruded edges sample the middle of the edge pixels, so filtering can't blend them
+        let pieces = [(start - extrusion, extrusion, 0.5, 0.0),
+                      (start, length, 0.0, length),
+                      (start + length, extrusion, length - 0.5, 0.0)];
+        pieces.iter().cloned().filter(|piece| piece.1 > 0.0).collect::<Vec<_>>()
+    };
+    let columns = axis(position.x, size.x);
+    let rows = axis(position.y, size.y);
+    add_slices(window, image, Transform::identity(), Color::white(), 0.0, &columns, &rows);
+}
+
+/// Place rectangles onto as many pages as needed, returning the page and top left of each one
+///
+/// This is the packing `AtlasPacker` uses, for tools that pack images without a Window. If a
+/// rectangle is too large for an empty page, its index is returned in an error.
+//
+// The skyline is the top edge of everything placed so far, as (x, y, width) segments. Each
+// rectangle goes where it would sit lowest on the skyline, and the tallest are placed first.
+pub fn pack_rectangles(sizes: &[(u32, u32)], page: (u32, u32)) -> Result<Vec<(usize, u32, u32)>, usize> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|&a, &b| (sizes[b].1, sizes[b].0).cmp(&(sizes[a].1, sizes[a].0)));
+    let mut skylines: Vec<Vec<(u32, u32, u32)>> = Vec::new();
+    let mut placements = vec![(0, 0, 0); sizes.len()];
+    for index in order {
+        let size = sizes[index];
+        let found = skylines.iter().enumerate()
+            .filter_map(|(page_index, skyline)| Some((page_index, find_position(skyline, size, page)?)))
+            .next();
+        let (page_index, (x, y)) = match found {
+            Some(found) => found,
+            None => {
+                let skyline = vec![(0, 0, page.0)];
+                let position = find_position(&skyline, size, page).ok_or(index)?;
+                skylines.push(skyline);
+                (skylines.len() - 1, position)
+            }
+        };
+        add_to_skyline(&mut skylines[page_index], x, y + size.1, size.0);
+        placements[index] = (page_index, x, y);
+    }
+    Ok(placements)
+}
+
+// Find the lowest (and then leftmost) position a rectangle fits at on top of the skyline
+fn find_position(skyline: &[(u32, u32, u32)], size: (u32, u32), page: (u32, u32)) -> Option<(u32, u32)> {
+    let mut best: Option<(u32, u32)> = None;
+    for (start, &(x, _, _)) in skyline.iter().enumerate() {
+        if x + size.0 > page.0 {
+            break;
+        }
+        // Rest the rectangle on the highest of the segments it spans
+        let y = skyline[start..].iter()
+            .take_while(|&&(segment_x, _, _)| segment_x < x + size.0)
+            .map(|&(_, y, _)| y)
+            .max()
+            .unwrap_or(0);
+        if y + size.1 <= page.1 && best.map(|(_, best_y)| y < best_y).unwrap_or(true) {
+            best = Some((x, y));
+        }
+    }
+    best
+}
+
+// Raise the skyline to the top of a newly placed rectangle
+fn add_to_skyline(skyline: &mut Vec<(u32, u32, u32)>, x: u32, top: u32, width: u32) {
+    let end = x + width;
+    // Keep the parts of the old segments on either side of the rectangle
+    let left = skyline.iter()
+        .filter(|&&(segment_x, _, _)| segment_x < x)
+        .map(|&(segment_x, y, segment_width)| (segment_x, y, (segment_x + segment_width).min(x) - segment_x));
+    let right = skyline.iter()
+        .filter(|&&(segment_x, _, segment_width)| segment_x + segment_width > end)
+        .map(|&(segment_x, y, segment_width)| (segment_x.max(end), y, segment_x + segment_width - segment_x.max(end)));
+    let segments: Vec<(u32, u32, u32)> = left.chain(Some((x, top, width))).chain(right).collect();
+    // Merge neighbouring segments at the same height
+    skyline.clear();
+    for segment in segments {
+        match skyline.last_mut() {
+            Some(last) if last.1 == segment.1 => last.2 += segment.2,
+            _ => skyline.push(segment)
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch="wasm32")))]
+mod tests {
+    use super::*;
+    use graphics::WindowBuilder;
+
+    #[test]
+    fn skyline() {
+        let mut skyline = vec![(0, 0, 100)];
+        add_to_skyline(&mut skyline, 0, 30, 40);
+        assert_eq!(skyline, vec![(0, 30, 40), (40, 0, 60)]);
+        add_to_skyline(&mut skyline, 40, 30, 20);
+        assert_eq!(skyline, vec![(0, 30, 60), (60, 0, 40)]);
+        assert_eq!(find_position(&skyline, (50, 10), (100, 100)), Some((0, 30)));
+        assert_eq!(find_position(&skyline, (30, 10), (100, 100)), Some((60, 0)));
+        assert_eq!(find_position(&skyline, (30, 80), (100, 100)), Some((60, 0)));
+        assert_eq!(find_position(&skyline, (50, 80), (100, 100)), None);
+    }
+
+    #[test]
+    fn pages() {
+        let sizes = [(10, 10), (60, 40), (60, 40), (60, 40), (30, 20)];
+        let placements = pack_rectangles(&sizes, (64, 64)).unwrap();
+        assert_eq!(placements[1], (0, 0, 0));
+        assert_eq!(placements[2], (1, 0, 0));
+        assert_eq!(placements[3], (2, 0, 0));
+        assert_eq!(placements[4], (0, 0, 40));
+        assert_eq!(placements[0], (0, 30, 40));
+        assert_eq!(pack_rectangles(&[(10, 10), (65, 10)], (64, 64)), Err(1));
+    }
+
+    #[test]
+    fn pack_atlas() {
+        let mut window = WindowBuilder::new("", 800, 600).build_headless();
+        let mut packer = AtlasPacker::new(64, 64).with_padding(1).with_extrusion(1);
+        packer.add_pixels("red", &[255, 0, 0, 255], 1, 1, PixelFormat::RGBA);
+        packer.add_frame("walk", 1, &Image::new_null(8, 8, PixelFormat::RGBA));
+        packer.add_frame("walk", 0, &Image::new_null(8, 16, PixelFormat::RGBA));
+        let atlas = packer.pack(&mut window).unwrap();
+        let walk = atlas.get("walk").unwrap().unwrap_animation();
+        assert_eq!(walk[0].area(), Rectangle::new(1, 1, 8, 16));
+        assert_eq!(walk[1].area(), Rectangle::new(12, 1, 8, 8));
+        assert_eq!(atlas.get("red").unwrap().unwrap_image().area(), Rectangle::new(23, 1, 1, 1));
+        // Each image is drawn as a nine-slice of itself and its extruded edges
+        assert_eq!(window.drawn_triangles.len(), 3 * 9 * 2);
+        let mut small = AtlasPacker::new(4, 4);
+        small.add_image("big", &Image::new_null(8, 8, PixelFormat::RGBA));
+        match small.pack(&mut window) {
+            Err(AtlasError::TooLarge(ref name)) => assert_eq!(name, "big"),
+            _ => panic!("The image should not fit")
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use ffi::gl;
+use graphics::{Color, Image, PixelFormat};
+use graphics::surface::current_framebuffer;
+#[cfg(not(target_arch="wasm32"))]
+use Result;
+use std::os::raw::c_void;
+#[cfg(not(target_arch="wasm32"))]
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///A copy of an image's pixels on the CPU, in rows of RGBA bytes from the top left
+///
+///Pixels are read back from an `Image`, a `Surface` or the `Window`, and can be checked directly,
+///saved as a PNG, or turned back into an Image.
+pub struct Pixels {
+    width: u32,
+    height: u32,
+    data: Vec<u8>
+}
+
+impl Pixels {
+    ///Create pixels from RGBA bytes, in rows from the top left
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Pixels {
+        assert_eq!(data.len(), (width * height * 4) as usize, "Pixels need 4 bytes for every pixel");
+        Pixels { width, height, data }
+    }
+
+    ///The width in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    ///The height in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    ///The RGBA bytes, in rows from the top left
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    ///Take the RGBA bytes, in rows from the top left
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    ///Get the color of the pixel at a given position from the top left
+    pub fn get(&self, x: u32, y: u32) -> Color {
+        assert!(x < self.width && y < self.height, "Pixel position out of bounds");
+        let index = ((y * self.width + x) * 4) as usize;
+        let channel = |offset: usize| self.data[index + offset] as f32 / 255.0;
+        Color { r: channel(0), g: channel(1), b: channel(2), a: channel(3) }
+    }
+
+    ///Upload the pixels to the GPU as a new image
+    pub fn to_image(&self) -> Image {
+        Image::from_raw(&self.data, self.width, self.height, PixelFormat::RGBA)
+    }
+
+    ///Encode the pixels as a PNG file
+    #[cfg(not(target_arch="wasm32"))]
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        use image::{ColorType, save_buffer};
+        Ok(save_buffer(path, &self.data, self.width, self.height, ColorType::RGBA(8))?)
+    }
+
+    // Read an area of a framebuffer, which has its rows from the bottom up for the window but from
+    // the top down for a texture
+    pub(crate) fn read(framebuffer: u32, x: i32, y: i32, width: u32, height: u32, flip: bool) -> Pixels {
+        let mut data = vec![0u8; (width * height * 4) as usize];
+        // There is nothing to read without a graphics context
+        if gl::is_headless() {
+            return Pixels { width, height, data };
+        }
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::ReadPixels(x, y, width as i32, height as i32, gl::RGBA, gl::UNSIGNED_BYTE,
+                           data.as_mut_ptr() as *mut c_void);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, current_framebuffer());
+        }
+        if flip {
+            flip_rows(&mut data, width as usize * 4);
+        }
+        Pixels { width, height, data }
+    }
+}
+
+// Reverse the order of the rows of an image
+pub(crate) fn flip_rows(data: &mut [u8], row_length: usize) {
+    let rows = data.len() / row_length;
+    for row in 0..rows / 2 {
+        let (top, bottom) = (row * row_length, (rows - row - 1) * row_length);
+        for offset in 0..row_length {
+            data.swap(top + offset, bottom + offset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flip() {
+        let mut data = vec![1, 1, 2, 2, 3, 3];
+        flip_rows(&mut data, 2);
+        assert_eq!(data, vec![3, 3, 2, 2, 1, 1]);
+        let mut even = vec![1, 2, 3, 4];
+        flip_rows(&mut even, 2);
+        assert_eq!(even, vec![3, 4, 1, 2]);
+    }
+
+    #[test]
+    fn get() {
+        let pixels = Pixels::new(2, 1, vec![0, 0, 0, 0, 255, 0, 0, 255]);
+        assert_eq!(pixels.get(1, 0), Color::red());
+        assert_eq!(pixels.get(0, 0).a, 0.0);
+    }
+
+    #[test]
+    #[cfg(not(target_arch="wasm32"))]
+    fn headless_screenshot() {
+        use graphics::WindowBuilder;
+        let mut window = WindowBuilder::new("", 40, 30).build_headless();
+        let screenshot = window.screenshot();
+        assert_eq!((screenshot.width(), screenshot.height()), (40, 30));
+        assert_eq!(screenshot.bytes().len(), 40 * 30 * 4);
+    }
+}
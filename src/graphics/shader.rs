@@ -0,0 +1,545 @@
+use ffi::gl;
+use geom::{Transform, Vector};
+use graphics::{Color, Image};
+use std::{
+    cell::RefCell,
+    error::Error,
+    ffi::CString,
+    fmt,
+    rc::Rc
+};
+use Result;
+
+#[cfg(not(target_arch="wasm32"))]
+pub(crate) const DEFAULT_VERTEX_SHADER: &str = r#"#version 150
+in vec2 position;
+in vec2 tex_coord;
+in vec4 color;
+in float uses_texture;
+in float tex_index;
+out vec4 Color;
+out vec2 Tex_coord;
+out float Uses_texture;
+out float Tex_index;
+void main() {
+    Color = color;
+    Tex_coord = tex_coord;
+    Uses_texture = uses_texture;
+    Tex_index = tex_index;
+    gl_Position = vec4(position, 0, 1);
+}"#;
+
+// Samplers can only be indexed by constants, so the texture is picked out with a chain of branches
+#[cfg(not(target_arch="wasm32"))]
+pub(crate) const DEFAULT_FRAGMENT_SHADER: &str = r#"#version 150
+in vec4 Color;
+in vec2 Tex_coord;
+in float Uses_texture;
+in float Tex_index;
+out vec4 outColor;
+uniform sampler2D tex[8];
+vec4 texel(int index, vec2 coord) {
+    if (index == 0) return texture(tex[0], coord);
+    if (index == 1) return texture(tex[1], coord);
+    if (index == 2) return texture(tex[2], coord);
+    if (index == 3) return texture(tex[3], coord);
+    if (index == 4) return texture(tex[4], coord);
+    if (index == 5) return texture(tex[5], coord);
+    if (index == 6) return texture(tex[6], coord);
+    return texture(tex[7], coord);
+}
+void main() {
+    vec4 tex_color = (Uses_texture != 0) ? texel(int(Tex_index + 0.5), Tex_coord) : vec4(1, 1, 1, 1);
+    outColor = Color * tex_color;
+}"#;
+
+#[cfg(target_arch="wasm32")]
+pub(crate) const DEFAULT_VERTEX_SHADER: &str = r#"attribute vec2 position;
+attribute vec2 tex_coord;
+attribute vec4 color;
+attribute lowp float uses_texture;
+attribute mediump float tex_index;
+varying vec2 Tex_coord;
+varying vec4 Color;
+varying lowp float Uses_texture;
+varying mediump float Tex_index;
+void main() {
+    gl_Position = vec4(position, 0, 1);
+    Tex_coord = tex_coord;
+    Color = color;
+    Uses_texture = uses_texture;
+    Tex_index = tex_index;
+}"#;
+
+#[cfg(target_arch="wasm32")]
+pub(crate) const DEFAULT_FRAGMENT_SHADER: &str = r#"varying highp vec4 Color;
+varying highp vec2 Tex_coord;
+varying lowp float Uses_texture;
+varying mediump float Tex_index;
+uniform sampler2D tex[8];
+highp vec4 texel(int index, highp vec2 coord) {
+    if (index == 0) return texture2D(tex[0], coord);
+    if (index == 1) return texture2D(tex[1], coord);
+    if (index == 2) return texture2D(tex[2], coord);
+    if (index == 3) return texture2D(tex[3], coord);
+    if (index == 4) return texture2D(tex[4], coord);
+    if (index == 5) return texture2D(tex[5], coord);
+    if (index == 6) return texture2D(tex[6], coord);
+    return texture2D(tex[7], coord);
+}
+void main() {
+    highp vec4 tex_color = (int(Uses_texture) != 0) ? texel(int(Tex_index + 0.5), Tex_coord) : vec4(1, 1, 1, 1);
+    gl_FragColor = Color * tex_color;
+}"#;
+
+// Meshes keep their vertices in their own space, so they are transformed and tinted on the GPU
+#[cfg(not(target_arch="wasm32"))]
+pub(crate) const MESH_VERTEX_SHADER: &str = r#"#version 150
+in vec2 position;
+in vec2 tex_coord;
+in vec4 color;
+in float uses_texture;
+in float tex_index;
+out vec4 Color;
+out vec2 Tex_coord;
+out float Uses_texture;
+out float Tex_index;
+uniform mat3 transform;
+uniform vec4 tint;
+void main() {
+    Color = color * tint;
+    Tex_coord = tex_coord;
+    Uses_texture = uses_texture;
+    Tex_index = tex_index;
+    gl_Position = vec4((transform * vec3(position, 1)).xy, 0, 1);
+}"#;
+
+#[cfg(target_arch="wasm32")]
+pub(crate) const MESH_VERTEX_SHADER: &str = r#"attribute vec2 position;
+attribute vec2 tex_coord;
+attribute vec4 color;
+attribute lowp float uses_texture;
+attribute mediump float tex_index;
+varying vec2 Tex_coord;
+varying vec4 Color;
+varying lowp float Uses_texture;
+varying mediump float Tex_index;
+uniform mat3 transform;
+uniform vec4 tint;
+void main() {
+    gl_Position = vec4((transform * vec3(position, 1)).xy, 0, 1);
+    Tex_coord = tex_coord;
+    Color = color * tint;
+    Uses_texture = uses_texture;
+    Tex_index = tex_index;
+}"#;
+
+// The number of textures the default shader can sample from in a single draw call
+pub(crate) const MAX_BATCH_TEXTURES: usize = 8;
+
+// The number of texture units WebGL guarantees, which headless shaders are limited to as well
+const MIN_TEXTURE_UNITS: usize = 8;
+
+// Every program binds its attributes to the same locations, so the vertex layout doesn't depend
+// on which shader is in use
+pub(crate) const ATTRIBUTES: [&str; 5] = ["position", "tex_coord", "color", "uses_texture", "tex_index"];
+
+/// A value that can be passed to a shader
+#[derive(Clone, Debug)]
+pub enum Uniform {
+    /// An `int` uniform
+    Int(i32),
+    /// A `float` uniform
+    Float(f32),
+    /// A `vec2` uniform
+    Vector(Vector),
+    /// A `vec4` uniform, in the order red, green, blue, alpha
+    Color(Color),
+    /// A `mat3` uniform
+    Transform(Transform),
+    /// A `sampler2D` uniform
+    ///
+    /// Each texture uniform is bound to its own texture unit, after the units used for the
+    /// `tex` samplers the images being drawn use
+    Texture(Image)
+}
+
+impl From<i32> for Uniform {
+    fn from(value: i32) -> Uniform {
+        Uniform::Int(value)
+    }
+}
+
+impl From<f32> for Uniform {
+    fn from(value: f32) -> Uniform {
+        Uniform::Float(value)
+    }
+}
+
+impl From<Vector> for Uniform {
+    fn from(value: Vector) -> Uniform {
+        Uniform::Vector(value)
+    }
+}
+
+impl From<Color> for Uniform {
+    fn from(value: Color) -> Uniform {
+        Uniform::Color(value)
+    }
+}
+
+impl From<Transform> for Uniform {
+    fn from(value: Transform) -> Uniform {
+        Uniform::Transform(value)
+    }
+}
+
+impl From<Image> for Uniform {
+    fn from(value: Image) -> Uniform {
+        Uniform::Texture(value)
+    }
+}
+
+impl<'a> From<&'a Image> for Uniform {
+    fn from(value: &'a Image) -> Uniform {
+        Uniform::Texture(value.clone())
+    }
+}
+
+#[derive(Debug)]
+struct ShaderData {
+    program: u32,
+    vertex: u32,
+    fragment: u32,
+    texture_locations: Vec<i32>,
+    texture_unit_limit: usize,
+    uniforms: RefCell<Vec<(String, i32, Uniform)>>
+}
+
+impl Drop for ShaderData {
+    fn drop(&mut self) {
+        // Shaders created without a graphics context have no program
+        if self.program != 0 {
+            unsafe {
+                gl::DeleteProgram(self.program);
+                gl::DeleteShader(self.fragment);
+                gl::DeleteShader(self.vertex);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// A compiled GLSL program that can replace the default shader with `Window::set_shader`
+///
+/// Custom shaders receive the same inputs as the default one: the vertex attributes `position`,
+/// `tex_coord`, `color`, `uses_texture` and `tex_index`, and the `tex` sampler for the image
+/// being drawn. A shader that declares `tex` as a single sampler draws one image per batch, while
+/// one that declares an array of up to 8 samplers can draw several, picking the sampler with
+/// `tex_index`. On desktop the shaders are GLSL 1.50, and on the web they are GLSL ES 1.00.
+///
+/// The `tex` samplers and the texture uniforms each take a texture unit, and together they can't
+/// use more than the GPU has. WebGL only guarantees 8.
+///
+/// Shaders are reference counted, so clones share the same program and uniforms.
+pub struct Shader {
+    data: Rc<ShaderData>
+}
+
+impl Shader {
+    /// Compile and link a shader program from the source of a vertex and a fragment shader
+    pub fn new(vertex_source: &str, fragment_source: &str) -> Result<Shader> {
+        if gl::is_headless() {
+            return Ok(Shader::from_data(ShaderData {
+                program: 0,
+                vertex: 0,
+                fragment: 0,
+                texture_locations: Vec::new(),
+                texture_unit_limit: MIN_TEXTURE_UNITS,
+                uniforms: RefCell::new(Vec::new())
+            }));
+        }
+        unsafe {
+            let vertex = compile(gl::VERTEX_SHADER, vertex_source).map_err(ShaderError::VertexCompile)?;
+            let fragment = match compile(gl::FRAGMENT_SHADER, fragment_source) {
+                Ok(fragment) => fragment,
+                Err(log) => {
+                    gl::DeleteShader(vertex);
+                    return Err(ShaderError::FragmentCompile(log).into());
+                }
+            };
+            let program = gl::CreateProgram();
+            gl::AttachShader(program, vertex);
+            gl::AttachShader(program, fragment);
+            for (location, name) in ATTRIBUTES.iter().enumerate() {
+                let raw = CString::new(*name).unwrap().into_raw();
+                gl::BindAttribLocation(program, location as u32, raw as *const i8);
+                #[cfg(not(target_arch="wasm32"))]
+                CString::from_raw(raw);
+            }
+            #[cfg(not(target_arch="wasm32"))] {
+                let raw = CString::new("out_color").unwrap().into_raw();
+                gl::BindFragDataLocation(program, 0, raw as *mut i8);
+                CString::from_raw(raw);
+            }
+            gl::LinkProgram(program);
+            let mut status: i32 = 0;
+            gl::GetProgramiv(program, gl::LINK_STATUS, &mut status as *mut i32);
+            if status as u8 != gl::TRUE {
+                let buffer: [u8; 512] = [0; 512];
+                let mut length = 0;
+                gl::GetProgramInfoLog(program, 512, &mut length as *mut i32, buffer.as_ptr() as *mut i8);
+                gl::DeleteProgram(program);
+                gl::DeleteShader(vertex);
+                gl::DeleteShader(fragment);
+                return Err(ShaderError::Link(log_string(&buffer, length)).into());
+            }
+            // A shader can declare `tex` as an array of samplers to take part in texture batching
+            let texture_locations = (0..MAX_BATCH_TEXTURES)
+                .map(|index| match index {
+                    0 => uniform_location(program, "tex"),
+                    index => uniform_location(program, &format!("tex[{}]", index))
+                })
+                .take_while(|&location| location != -1)
+                .collect::<Vec<_>>();
+            let mut texture_unit_limit = 0;
+            gl::GetIntegerv(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS, &mut texture_unit_limit as *mut i32);
+            let texture_unit_limit = texture_unit_limit.max(0) as usize;
+            let shader = Shader::from_data(ShaderData {
+                program,
+                vertex,
+                fragment,
+                texture_locations,
+                texture_unit_limit,
+                uniforms: RefCell::new(Vec::new())
+            });
+            check_texture_units(shader.texture_units(), texture_unit_limit)?;
+            Ok(shader)
+        }
+    }
+
+    /// Compile a shader program from the source of a fragment shader, using the default vertex shader
+    pub fn from_fragment(fragment_source: &str) -> Result<Shader> {
+        Shader::new(DEFAULT_VERTEX_SHADER, fragment_source)
+    }
+
+    /// The source of the vertex shader used by default, to base custom shaders on
+    pub fn default_vertex_source() -> &'static str {
+        DEFAULT_VERTEX_SHADER
+    }
+
+    /// The source of the fragment shader used by default, to base custom shaders on
+    pub fn default_fragment_source() -> &'static str {
+        DEFAULT_FRAGMENT_SHADER
+    }
+
+    fn from_data(data: ShaderData) -> Shader {
+        Shader { data: Rc::new(data) }
+    }
+
+    /// Set the value of a uniform in the shader
+    ///
+    /// The value is sent to the GPU every time items drawn with the shader are flushed, so
+    /// changing it affects everything drawn after the last flush. Setting a uniform the program
+    /// doesn't use does nothing.
+    ///
+    /// Texture uniforms are bound to the texture units after the ones the `tex` samplers use, so
+    /// adding one fails if the GPU has no texture units left.
+    pub fn set_uniform<U: Into<Uniform>>(&self, name: &str, value: U) -> Result<()> {
+        let value = value.into();
+        if let Uniform::Texture(_) = value {
+            let uniforms = self.data.uniforms.borrow();
+            let textures = uniforms.iter()
+                .filter(|uniform| uniform.0 != name)
+                .filter(|uniform| match uniform.2 { Uniform::Texture(_) => true, _ => false })
+                .count();
+            check_texture_units(self.texture_units() + textures + 1, self.data.texture_unit_limit)?;
+        }
+        self.store_uniform(name, value);
+        Ok(())
+    }
+
+    // Set a uniform without checking it has a texture unit, for uniforms that aren't textures
+    pub(crate) fn store_uniform(&self, name: &str, value: Uniform) {
+        let mut uniforms = self.data.uniforms.borrow_mut();
+        if let Some(uniform) = uniforms.iter_mut().find(|uniform| uniform.0 == name) {
+            uniform.2 = value;
+            return;
+        }
+        let location = if self.data.program == 0 { -1 } else { uniform_location(self.data.program, name) };
+        uniforms.push((name.to_owned(), location, value));
+    }
+
+    /// Get the value a uniform was last set to
+    pub fn uniform(&self, name: &str) -> Option<Uniform> {
+        self.data.uniforms.borrow().iter()
+            .find(|uniform| uniform.0 == name)
+            .map(|uniform| uniform.2.clone())
+    }
+
+    pub(crate) fn program(&self) -> u32 {
+        self.data.program
+    }
+
+    pub(crate) fn texture_locations(&self) -> &[i32] {
+        &self.data.texture_locations
+    }
+
+    // The number of textures a batch drawn with the shader can use
+    pub(crate) fn texture_units(&self) -> usize {
+        self.data.texture_locations.len().max(1)
+    }
+
+    // Send the uniform values to the GPU, binding texture uniforms to the texture units after the
+    // ones the `tex` samplers use
+    pub(crate) unsafe fn apply_uniforms(&self) {
+        let mut unit = self.texture_units() as u32;
+        for &(_, location, ref value) in self.data.uniforms.borrow().iter() {
+            match *value {
+                Uniform::Int(value) => gl::Uniform1i(location, value),
+                Uniform::Float(value) => gl::Uniform1f(location, value),
+                Uniform::Vector(value) => gl::Uniform2f(location, value.x, value.y),
+                Uniform::Color(value) => gl::Uniform4f(location, value.r, value.g, value.b, value.a),
+                Uniform::Transform(value) => {
+                    // GLSL matrices are column-major
+                    let mut matrix = [0f32; 9];
+                    for column in 0..3 {
+                        for row in 0..3 {
+                            matrix[column * 3 + row] = value.0[row][column];
+                        }
+                    }
+                    gl::UniformMatrix3fv(location, 1, gl::FALSE, matrix.as_ptr());
+                }
+                Uniform::Texture(ref image) => {
+                    gl::ActiveTexture(gl::TEXTURE0 + unit);
+                    gl::BindTexture(gl::TEXTURE_2D, image.get_id());
+                    gl::Uniform1i(location, unit as i32);
+                    unit += 1;
+                }
+            }
+        }
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+}
+
+// Make sure the texture units a shader needs are within what the GPU has
+fn check_texture_units(needed: usize, limit: usize) -> ::std::result::Result<(), ShaderError> {
+    if needed > limit {
+        Err(ShaderError::TextureUnits(needed, limit))
+    } else {
+        Ok(())
+    }
+}
+
+unsafe fn compile(shader_type: u32, source: &str) -> ::std::result::Result<u32, String> {
+    let shader = gl::CreateShader(shader_type);
+    let text = CString::new(source).unwrap().into_raw();
+    gl::ShaderSource(shader, text);
+    gl::CompileShader(shader);
+    #[cfg(not(target_arch="wasm32"))]
+    CString::from_raw(text);
+    let mut status: i32 = 0;
+    gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status as *mut i32);
+    if status as u8 != gl::TRUE {
+        let buffer: [u8; 512] = [0; 512];
+        let mut length = 0;
+        gl::GetShaderInfoLog(shader, 512, &mut length as *mut i32, buffer.as_ptr() as *mut i8);
+        gl::DeleteShader(shader);
+        return Err(log_string(&buffer, length));
+    }
+    Ok(shader)
+}
+
+fn log_string(buffer: &[u8], length: i32) -> String {
+    String::from_utf8_lossy(&buffer[..(length.max(0) as usize).min(buffer.len())]).into_owned()
+}
+
+fn uniform_location(program: u32, name: &str) -> i32 {
+    let raw = CString::new(name).unwrap().into_raw();
+    unsafe {
+        let location = gl::GetUniformLocation(program, raw as *const i8);
+        #[cfg(not(target_arch="wasm32"))]
+        CString::from_raw(raw);
+        location
+    }
+}
+
+#[derive(Debug)]
+/// An error generated while compiling or linking a shader
+pub enum ShaderError {
+    /// The vertex shader failed to compile, with the compiler's log
+    VertexCompile(String),
+    /// The fragment shader failed to compile, with the compiler's log
+    FragmentCompile(String),
+    /// The program failed to link, with the linker's log
+    Link(String),
+    /// The samplers and texture uniforms need more texture units than the GPU has, with the
+    /// number needed and the number available
+    TextureUnits(usize, usize)
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ShaderError::VertexCompile(ref log) => write!(f, "Vertex shader compilation failed: {}", log),
+            &ShaderError::FragmentCompile(ref log) => write!(f, "Fragment shader compilation failed: {}", log),
+            &ShaderError::Link(ref log) => write!(f, "Shader program linking failed: {}", log),
+            &ShaderError::TextureUnits(needed, limit) =>
+                write!(f, "The shader needs {} texture units, but only {} are available", needed, limit)
+        }
+    }
+}
+
+impl Error for ShaderError {
+    fn description(&self) -> &str {
+        match self {
+            &ShaderError::VertexCompile(_) => "Vertex shader compilation failed",
+            &ShaderError::FragmentCompile(_) => "Fragment shader compilation failed",
+            &ShaderError::Link(_) => "Shader program linking failed",
+            &ShaderError::TextureUnits(_, _) => "The shader needs more texture units than are available"
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch="wasm32")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniforms() {
+        gl::set_headless(true);
+        let shader = Shader::from_fragment(Shader::default_fragment_source()).unwrap();
+        shader.set_uniform("time", 1.5).unwrap();
+        shader.set_uniform("offset", Vector::new(1, 2)).unwrap();
+        shader.clone().set_uniform("time", 2.5).unwrap();
+        match shader.uniform("time") {
+            Some(Uniform::Float(time)) => assert_eq!(time, 2.5),
+            other => panic!("Unexpected uniform {:?}", other)
+        }
+        assert!(shader.uniform("missing").is_none());
+    }
+
+    #[test]
+    fn texture_units() {
+        gl::set_headless(true);
+        let shader = Shader::from_fragment(Shader::default_fragment_source()).unwrap();
+        let image = Image::new_null(4, 4, ::graphics::PixelFormat::RGBA);
+        // The `tex` sampler takes the first of the 8 units
+        for index in 0..7 {
+            shader.set_uniform(&format!("layer{}", index), &image).unwrap();
+        }
+        shader.set_uniform("layer0", &image).unwrap();
+        match shader.set_uniform("layer7", &image) {
+            Err(::QuicksilverError::ShaderError(ShaderError::TextureUnits(9, 8))) => (),
+            other => panic!("Unexpected result {:?}", other)
+        }
+        assert!(shader.uniform("layer7").is_none());
+        shader.set_uniform("layer7", 1.0).unwrap();
+    }
+
+    #[test]
+    fn error_log() {
+        let error: ::QuicksilverError = ShaderError::FragmentCompile("0:3: 'colour' : undeclared identifier".to_owned()).into();
+        assert_eq!(error.to_string(), "Fragment shader compilation failed: 0:3: 'colour' : undeclared identifier");
+    }
+}
@@ -0,0 +1,232 @@
+#[cfg(feature="fonts")]
+use geom::{Rectangle, Vector};
+#[cfg(feature="fonts")]
+use graphics::{Color, Draw, Drawable, Font, Image, Window};
+#[cfg(feature="fonts")]
+use timer::current_time;
+
+/// Counters and timings gathered over a single frame
+///
+/// A frame is one pass of the game loop: any updates that were due, followed by a draw. Get the
+/// statistics for the most recent frame from `Window::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    /// The number of times a batch of triangles was sent to the GPU
+    pub draw_calls: u32,
+    /// The number of times a batch ran out of texture units, each of which breaks a batch
+    pub texture_switches: u32,
+    /// The number of times the texture changed between triangles in the same batch, each of
+    /// which would have been a separate draw call without texture batching
+    pub draw_calls_saved: u32,
+    /// The number of vertices flushed from the Window to the GPU
+    pub vertices: u32,
+    /// The number of triangles flushed from the Window to the GPU
+    pub triangles: u32,
+    /// The number of updates that ran during the frame
+    pub updates: u32,
+    /// The number of milliseconds spent in `State::update`
+    pub update_time: f64,
+    /// The number of milliseconds spent in `State::draw`, not counting `present_time`
+    pub draw_time: f64,
+    /// The number of milliseconds spent in `Window::present` swapping buffers, which includes
+    /// waiting for vsync
+    pub present_time: f64,
+    /// The number of updates that actually ran over the last full second
+    pub ticks_per_second: u32
+}
+
+// Accumulates the statistics of the frame in progress and keeps those of the last finished one
+pub(crate) struct StatsCollector {
+    pending: FrameStats,
+    last: FrameStats,
+    second_start: f64,
+    second_ticks: u32,
+    ticks_per_second: u32,
+    // The time spent presenting since the last draw was recorded, which the draw doesn't count
+    presenting: f64
+}
+
+impl StatsCollector {
+    pub fn new(now: f64) -> StatsCollector {
+        StatsCollector {
+            pending: FrameStats::default(),
+            last: FrameStats::default(),
+            second_start: now,
+            second_ticks: 0,
+            ticks_per_second: 0,
+            presenting: 0.0
+        }
+    }
+
+    pub fn last(&self) -> FrameStats {
+        self.last
+    }
+
+    pub fn record_flush(&mut self, vertices: usize, triangles: usize) {
+        self.pending.vertices += vertices as u32;
+        self.pending.triangles += triangles as u32;
+    }
+
+    pub fn record_update(&mut self, time: f64) {
+        self.pending.updates += 1;
+        self.pending.update_time += time;
+        self.second_ticks += 1;
+    }
+
+    pub fn record_draw(&mut self, time: f64) {
+        self.pending.draw_time += time - self.presenting;
+        self.presenting = 0.0;
+    }
+
+    pub fn record_present(&mut self, time: f64) {
+        self.pending.present_time += time;
+        self.presenting += time;
+    }
+
+    pub fn finish(&mut self, now: f64, draw_calls: u32, texture_switches: u32, draw_calls_saved: u32) {
+        if now - self.second_start >= 1000.0 {
+            self.ticks_per_second = self.second_ticks;
+            self.second_ticks = 0;
+            self.second_start = now;
+        }
+        self.last = FrameStats {
+            draw_calls,
+            texture_switches,
+            draw_calls_saved,
+            ticks_per_second: self.ticks_per_second,
+            ..self.pending
+        };
+        self.pending = FrameStats::default();
+    }
+}
+
+/// An on-screen display of the frame statistics, rendered with a Font
+///
+/// Call `update` once per frame with `Window::stats`, and then draw it like any other Drawable.
+/// The text is only re-rendered every so often, so that the numbers are readable.
+#[cfg(feature="fonts")]
+pub struct StatsOverlay {
+    font: Font,
+    size: f32,
+    color: Color,
+    background: Color,
+    position: Vector,
+    refresh_rate: f64,
+    refreshed: Option<f64>,
+    lines: Vec<Image>
+}
+
+#[cfg(feature="fonts")]
+impl StatsOverlay {
+    /// Create an overlay in the top left of the screen that renders with the given font
+    pub fn new(font: Font) -> StatsOverlay {
+        StatsOverlay {
+            font,
+            size: 16.0,
+            color: Color::white(),
+            background: Color::black().with_alpha(0.5),
+            position: Vector::zero(),
+            refresh_rate: 500.0,
+            refreshed: None,
+            lines: Vec::new()
+        }
+    }
+
+    /// Set the font size of the text (defaults to 16)
+    pub fn with_size(self, size: f32) -> StatsOverlay {
+        StatsOverlay { size, ..self }
+    }
+
+    /// Set the color of the text (defaults to white)
+    pub fn with_color(self, color: Color) -> StatsOverlay {
+        StatsOverlay { color, ..self }
+    }
+
+    /// Set the color of the box behind the text (defaults to translucent black)
+    pub fn with_background(self, background: Color) -> StatsOverlay {
+        StatsOverlay { background, ..self }
+    }
+
+    /// Set where the top left of the overlay is (defaults to the origin)
+    pub fn with_position(self, position: Vector) -> StatsOverlay {
+        StatsOverlay { position, ..self }
+    }
+
+    /// Set the number of milliseconds between refreshes of the text (defaults to 500)
+    pub fn with_refresh_rate(self, refresh_rate: f64) -> StatsOverlay {
+        StatsOverlay { refresh_rate, ..self }
+    }
+
+    /// Re-render the text from the given statistics if it is due for a refresh
+    pub fn update(&mut self, stats: FrameStats) {
+        let now = current_time();
+        if let Some(refreshed) = self.refreshed {
+            if now - refreshed < self.refresh_rate {
+                return;
+            }
+        }
+        self.refreshed = Some(now);
+        let text = [
+            format!("{} ticks/s, {} updates", stats.ticks_per_second, stats.updates),
+            format!("update {:.2} ms, draw {:.2} ms, present {:.2} ms", stats.update_time, stats.draw_time, stats.present_time),
+            format!("{} draw calls, {} saved, {} texture switches", stats.draw_calls, stats.draw_calls_saved, stats.texture_switches),
+            format!("{} vertices, {} triangles", stats.vertices, stats.triangles)
+        ];
+        self.lines = text.iter().map(|line| self.font.render(line, self.size, self.color)).collect();
+    }
+}
+
+#[cfg(feature="fonts")]
+impl Drawable for StatsOverlay {
+    fn draw(&self, window: &mut Window) {
+        let width = self.lines.iter().map(|line| line.area().width).fold(0.0, f32::max);
+        let height = self.size * self.lines.len() as f32;
+        let z = ::std::f32::MAX;
+        window.draw(&Draw::rectangle(Rectangle::newv(self.position, Vector::new(width, height)))
+            .with_color(self.background)
+            .with_z(z));
+        for (i, line) in self.lines.iter().enumerate() {
+            let top_left = self.position + Vector::new(0.0, self.size * i as f32);
+            window.draw(&Draw::image(line, top_left + line.area().size() / 2).with_z(z));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finish_frame() {
+        let mut stats = StatsCollector::new(0.0);
+        stats.record_update(2.0);
+        stats.record_update(3.0);
+        stats.record_present(3.0);
+        stats.record_draw(7.0);
+        stats.record_flush(8, 4);
+        stats.record_flush(4, 2);
+        stats.finish(16.0, 3, 1, 5);
+        let last = stats.last();
+        assert_eq!(last.updates, 2);
+        assert_eq!(last.update_time, 5.0);
+        assert_eq!(last.draw_time, 4.0);
+        assert_eq!(last.present_time, 3.0);
+        assert_eq!(last.vertices, 12);
+        assert_eq!(last.triangles, 6);
+        assert_eq!(last.draw_calls, 3);
+        assert_eq!(last.texture_switches, 1);
+        assert_eq!(last.draw_calls_saved, 5);
+        stats.finish(32.0, 0, 0, 0);
+        assert_eq!(stats.last(), FrameStats::default());
+    }
+
+    #[test]
+    fn ticks_per_second() {
+        let mut stats = StatsCollector::new(0.0);
+        for frame in 1..71 {
+            stats.record_update(0.0);
+            stats.finish(frame as f64 * 1000.0 / 60.0, 0, 0, 0);
+        }
+        assert_eq!(stats.last().ticks_per_second, 60);
+    }
+}
@@ -1,68 +1,207 @@
 use ffi::gl;
 use geom::{Transform, Vector};
-use graphics::{Image, PixelFormat, Window, View};
-use std::rc::Rc;
+use graphics::{Color, Image, PixelFormat, Pixels, Window, View};
+use std::{
+    cell::RefCell,
+    rc::Rc
+};
+
+thread_local! {
+    // The framebuffers being rendered to, innermost last, with the viewports to restore after each
+    static TARGETS: RefCell<Vec<(u32, [i32; 4])>> = RefCell::new(Vec::new());
+}
+
+// The framebuffer that drawing currently goes to, which is 0 for the window
+pub(crate) fn current_framebuffer() -> u32 {
+    TARGETS.with(|targets| targets.borrow().last().map(|&(framebuffer, _)| framebuffer).unwrap_or(0))
+}
 
 #[derive(Debug)]
 struct SurfaceData {
-    framebuffer: u32
+    framebuffer: u32,
+    renderbuffer: u32
 }
 
 impl Drop for SurfaceData {
     fn drop(&mut self) {
-        unsafe { gl::DeleteFramebuffer(self.framebuffer) };
+        if self.renderbuffer != 0 {
+            unsafe { gl::DeleteRenderbuffer(self.renderbuffer) };
+        }
+        if self.framebuffer != 0 {
+            unsafe { gl::DeleteFramebuffer(self.framebuffer) };
+        }
     }
 }
 
 #[derive(Clone, Debug)]
 ///A possible render target that can be drawn to the screen
+///
+///Besides its color, a surface can have a depth and a stencil buffer, for custom shaders and
+///drawing code that use them.
 pub struct Surface {
     image: Image,
     data: Rc<SurfaceData>,
+    depth: bool,
+    stencil: bool
 }
 
 impl Surface {
     ///Create a new surface with a given width and height
     pub fn new(width: u32, height: u32) -> Surface {
+        Surface::create(width, height, false, false)
+    }
+
+    ///Create a new surface with a depth buffer, a stencil buffer, or both
+    pub fn with_buffers(width: u32, height: u32, depth: bool, stencil: bool) -> Surface {
+        Surface::create(width, height, depth, stencil)
+    }
+
+    fn create(width: u32, height: u32, depth: bool, stencil: bool) -> Surface {
         let image = Image::new_null(width, height, PixelFormat::RGBA);
-        let surface = SurfaceData {
-            framebuffer: unsafe { gl::GenFramebuffer() }
+        if gl::is_headless() {
+            return Surface {
+                image,
+                data: Rc::new(SurfaceData { framebuffer: 0, renderbuffer: 0 }),
+                depth,
+                stencil
+            };
+        }
+        let mut surface = SurfaceData {
+            framebuffer: unsafe { gl::GenFramebuffer() },
+            renderbuffer: 0
         };
-        unsafe { 
+        unsafe {
             gl::BindFramebuffer(gl::FRAMEBUFFER, surface.framebuffer);
             gl::FramebufferTexture(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, image.get_id(), 0);
             gl::DrawBuffer(gl::COLOR_ATTACHMENT0);
+            // Depth and stencil share a buffer when there are both
+            let buffer = match (depth, stencil) {
+                (true, true) => Some((gl::DEPTH24_STENCIL8, gl::DEPTH_STENCIL_ATTACHMENT)),
+                (true, false) => Some((gl::DEPTH_COMPONENT24, gl::DEPTH_ATTACHMENT)),
+                (false, true) => Some((gl::STENCIL_INDEX8, gl::STENCIL_ATTACHMENT)),
+                (false, false) => None
+            };
+            if let Some((format, attachment)) = buffer {
+                surface.renderbuffer = gl::GenRenderbuffer();
+                gl::BindRenderbuffer(gl::RENDERBUFFER, surface.renderbuffer);
+                gl::RenderbufferStorage(gl::RENDERBUFFER, format, width as i32, height as i32);
+                gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment, gl::RENDERBUFFER, surface.renderbuffer);
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, current_framebuffer());
         }
         Surface {
             image,
-            data: Rc::new(surface)
+            data: Rc::new(surface),
+            depth,
+            stencil
         }
     }
 
     ///Render data to the surface
     ///
-    ///Do not attempt to use the surface or its image within the function, because it is undefined behavior
+    ///Surfaces can be rendered to within each other's `render_to`, and drawing returns to the
+    ///outer surface afterwards. Rendering to a surface within its own `render_to` panics, and
+    ///drawing a surface's image within its own `render_to` is undefined behavior.
     pub fn render_to<F>(&self, window: &mut Window, func: F) where F: FnOnce(&mut Window) {
-        let viewport = &mut [0, 0, 0, 0];
+        let framebuffer = self.data.framebuffer;
+        // Headless surfaces have no framebuffer, so they can't be told apart
+        let nested = framebuffer != 0 && TARGETS.with(|targets| targets.borrow().iter().any(|&(target, _)| target == framebuffer));
+        assert!(!nested, "A Surface can't be rendered to within its own render_to");
+        window.flush();
         let view = window.view();
-        unsafe {
-            gl::GetViewport(viewport.as_mut_ptr());
-            gl::BindFramebuffer(gl::FRAMEBUFFER, self.data.framebuffer);
-            gl::Viewport(0, 0, self.image.source_width() as i32, self.image.source_height() as i32);
-            window.flush();
-            window.set_view(View::new_transformed(self.image.area(), Transform::scale(Vector::new(1, -1))));
+        let mut viewport = [0, 0, 0, 0];
+        if !gl::is_headless() {
+            unsafe {
+                gl::GetViewport(viewport.as_mut_ptr());
+                gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+                gl::Viewport(0, 0, self.image.source_width() as i32, self.image.source_height() as i32);
+            }
         }
+        TARGETS.with(|targets| targets.borrow_mut().push((framebuffer, viewport)));
+        window.set_view(View::new_transformed(self.image.area(), Transform::scale(Vector::new(1, -1))));
         func(window);
+        window.flush();
         window.set_view(view);
-        unsafe {
-            window.flush();
-            gl::BindFramebuffer(gl::FRAMEBUFFER, 0); 
-            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        TARGETS.with(|targets| targets.borrow_mut().pop());
+        if !gl::is_headless() {
+            unsafe {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, current_framebuffer());
+                gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+            }
         }
     }
 
+    ///Clear the surface to a color, along with its depth and stencil buffers
+    pub fn clear(&self, window: &mut Window, color: Color) {
+        self.render_to(window, |window| window.clear(color));
+    }
+
+    ///Replace the surface with an empty one of a different size
+    ///
+    ///The surface keeps its depth and stencil buffers. Clones of the surface and its image from
+    ///before the resize are left as they were.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        *self = Surface::create(width, height, self.depth, self.stencil);
+    }
+
+    ///Get the size of the surface in pixels
+    pub fn size(&self) -> Vector {
+        self.image.source_size()
+    }
+
+    ///Check if the surface has a depth buffer
+    pub fn has_depth(&self) -> bool {
+        self.depth
+    }
+
+    ///Check if the surface has a stencil buffer
+    pub fn has_stencil(&self) -> bool {
+        self.stencil
+    }
+
+    ///Read what has been drawn to the surface back from the GPU
+    pub fn read_pixels(&self) -> Pixels {
+        Pixels::read(self.data.framebuffer, 0, 0, self.image.source_width(), self.image.source_height(), false)
+    }
+
     ///Get a reference to the Image that contains the data drawn to the Surface
     pub fn image(&self) -> &Image {
         &self.image
     }
 }
+
+#[cfg(all(test, not(target_arch="wasm32")))]
+mod tests {
+    use super::*;
+    use graphics::{Draw, WindowBuilder};
+    use geom::Rectangle;
+
+    #[test]
+    fn nested() {
+        let mut window = WindowBuilder::new("", 800, 600).build_headless();
+        let outer = Surface::new(64, 32);
+        let inner = Surface::with_buffers(16, 16, true, false);
+        outer.render_to(&mut window, |window| {
+            inner.render_to(window, |window| {
+                assert_eq!(window.view().normalize * Vector::new(16, 16), Vector::new(1, 0));
+            });
+            // The outer surface's view comes back after the inner one is done
+            assert_eq!(window.view().normalize * Vector::new(64, 32), Vector::new(1, 0));
+            window.draw(&Draw::rectangle(Rectangle::new(0, 0, 4, 4)));
+        });
+        assert_eq!(window.view().normalize * Vector::new(800, 600), Vector::one());
+        assert_eq!(current_framebuffer(), 0);
+        assert_eq!(window.drawn_triangles.len(), 2);
+    }
+
+    #[test]
+    fn resize() {
+        let _window = WindowBuilder::new("", 800, 600).build_headless();
+        let mut surface = Surface::with_buffers(8, 8, true, true);
+        let old = surface.clone();
+        surface.resize(20, 10);
+        assert_eq!(surface.size(), Vector::new(20, 10));
+        assert_eq!(old.size(), Vector::new(8, 8));
+        assert!(surface.has_depth() && surface.has_stencil());
+    }
+}
@@ -0,0 +1,483 @@
+use geom::{Scalar, Transform, Vector};
+use std::f32::consts::PI;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The shape drawn at the open ends of a line
+pub enum LineCap {
+    /// The line stops exactly at its end points
+    Butt,
+    /// The line extends past its end points by half its thickness
+    Square,
+    /// The line ends in a half circle around each end point
+    Round
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The shape drawn on the outside of a corner where two segments of a line meet
+pub enum LineJoin {
+    /// The edges of the segments are extended until they meet in a point
+    ///
+    /// Very sharp corners fall back to a bevel, so that the point doesn't reach far past the line
+    Miter,
+    /// The corner is cut off flat
+    Bevel,
+    /// The corner is rounded off
+    Round
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// Where the outline of a shape is drawn relative to the shape's edge
+pub enum StrokeAlignment {
+    /// The outline is entirely inside the shape
+    Inside,
+    /// The outline is centered on the edge of the shape
+    Center,
+    /// The outline is entirely outside the shape
+    Outside
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The way a line is drawn: how thick it is, and the shape of its ends and corners
+pub struct Stroke {
+    thickness: f32,
+    cap: LineCap,
+    join: LineJoin,
+    miter_limit: f32,
+    alignment: StrokeAlignment
+}
+
+impl Stroke {
+    /// Create a stroke of the given thickness, with butt caps, miter joins and centered alignment
+    pub fn new<T: Scalar>(thickness: T) -> Stroke {
+        Stroke {
+            thickness: thickness.float(),
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+            miter_limit: 4.0,
+            alignment: StrokeAlignment::Center
+        }
+    }
+
+    /// Set the shape of the ends of the line
+    pub fn with_cap(self, cap: LineCap) -> Stroke {
+        Stroke { cap, ..self }
+    }
+
+    /// Set the shape of the corners of the line
+    pub fn with_join(self, join: LineJoin) -> Stroke {
+        Stroke { join, ..self }
+    }
+
+    /// Set how far a miter join can reach, as a multiple of half the thickness (defaults to 4)
+    pub fn with_miter_limit(self, miter_limit: f32) -> Stroke {
+        Stroke { miter_limit, ..self }
+    }
+
+    /// Set where the outline of a shape is drawn relative to its edge
+    ///
+    /// This only applies to outlines, because an open line has no inside or outside
+    pub fn with_alignment(self, alignment: StrokeAlignment) -> Stroke {
+        Stroke { alignment, ..self }
+    }
+
+    /// The thickness of the line
+    pub fn thickness(&self) -> f32 {
+        self.thickness
+    }
+
+    /// The shape of the ends of the line
+    pub fn cap(&self) -> LineCap {
+        self.cap
+    }
+
+    /// The shape of the corners of the line
+    pub fn join(&self) -> LineJoin {
+        self.join
+    }
+
+    /// Where the outline of a shape is drawn relative to its edge
+    pub fn alignment(&self) -> StrokeAlignment {
+        self.alignment
+    }
+}
+
+impl Default for Stroke {
+    fn default() -> Stroke {
+        Stroke::new(1)
+    }
+}
+
+// Points and the triangles between them, produced by tessellating a shape
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Geometry {
+    pub points: Vec<Vector>,
+    pub triangles: Vec<[u32; 3]>
+}
+
+impl Geometry {
+    fn add_point(&mut self, point: Vector) -> u32 {
+        self.points.push(point);
+        self.points.len() as u32 - 1
+    }
+
+    fn add_triangle(&mut self, a: Vector, b: Vector, c: Vector) {
+        let indices = [self.add_point(a), self.add_point(b), self.add_point(c)];
+        self.triangles.push(indices);
+    }
+
+    fn add_quad(&mut self, a: Vector, b: Vector, c: Vector, d: Vector) {
+        let indices = [self.add_point(a), self.add_point(b), self.add_point(c), self.add_point(d)];
+        self.triangles.push([indices[0], indices[1], indices[2]]);
+        self.triangles.push([indices[2], indices[3], indices[0]]);
+    }
+
+    // Add a fan of triangles around a center, starting at an offset from it and sweeping by an
+    // angle, with a full turn made of the given number of segments
+    fn add_arc(&mut self, center: Vector, from: Vector, sweep: f32, full: usize) {
+        let segments = arc_segments(full, sweep);
+        let rotation = Transform::rotate(sweep / segments as f32);
+        let center_index = self.add_point(center);
+        let mut arrow = from;
+        let mut previous = self.add_point(center + arrow);
+        for _ in 0..segments {
+            arrow = rotation * arrow;
+            let next = self.add_point(center + arrow);
+            self.triangles.push([center_index, previous, next]);
+            previous = next;
+        }
+    }
+}
+
+// The number of segments part of a turn needs, given how many a full turn is made of
+pub(crate) fn arc_segments(full: usize, sweep: f32) -> usize {
+    ((full as f32 * sweep.abs() / 360.0).ceil() as usize).max(1)
+}
+
+// The number of segments a full circle needs to look smooth at a radius in pixels
+//
+// Each segment cuts across the circle, so it is made short enough that its middle is never more
+// than a quarter of a pixel inside the true edge.
+pub(crate) fn circle_segments(radius: f32) -> usize {
+    const TOLERANCE: f32 = 0.25;
+    if radius <= TOLERANCE {
+        return 6;
+    }
+    let angle = 2.0 * (1.0 - TOLERANCE / radius).acos();
+    ((2.0 * PI / angle).ceil() as usize).max(6).min(512)
+}
+
+// Find points along the edge of an ellipse, from a start angle sweeping through an angle in degrees
+//
+// A full turn doesn't repeat its first point at the end, while part of a turn includes both ends
+pub(crate) fn ellipse_points(center: Vector, radii: Vector, start: f32, sweep: f32, segments: usize) -> Vec<Vector> {
+    let full = sweep.abs() >= 360.0;
+    let count = if full { segments } else { segments + 1 };
+    let sweep = if !full { sweep } else if sweep < 0.0 { -360.0 } else { 360.0 };
+    (0..count)
+        .map(|i| Vector::from_angle(start + sweep * i as f32 / segments as f32).times(radii) + center)
+        .collect()
+}
+
+// Fill a shape that every point can be seen from the center of, with triangles that fan out from it
+pub(crate) fn fan(center: Vector, points: &[Vector], closed: bool) -> Geometry {
+    let mut geometry = Geometry::default();
+    let center_index = geometry.add_point(center);
+    geometry.points.extend_from_slice(points);
+    let edges = if closed { points.len() } else { points.len().saturating_sub(1) };
+    for i in 0..edges {
+        let next = (i + 1) % points.len();
+        geometry.triangles.push([center_index, i as u32 + 1, next as u32 + 1]);
+    }
+    geometry
+}
+
+// Find the triangles that fill a simple polygon, which may be concave and in either winding order
+//
+// This uses ear clipping: any corner of the polygon that is convex and has no other points inside
+// it can be cut off as a triangle, until only one triangle is left. Self-intersecting polygons
+// have no such corners at some point, so a corner is cut off regardless to guarantee progress.
+pub(crate) fn triangulate(points: &[Vector]) -> Vec<[u32; 3]> {
+    let count = points.len();
+    if count < 3 {
+        return Vec::new();
+    }
+    let area: f32 = (0..count).map(|i| points[i].cross(points[(i + 1) % count])).sum();
+    let winding = if area < 0.0 { -1.0 } else { 1.0 };
+    let mut remaining: Vec<usize> = (0..count).collect();
+    let mut triangles = Vec::with_capacity(count - 2);
+    let mut index = 0;
+    let mut failures = 0;
+    while remaining.len() > 3 {
+        let length = remaining.len();
+        index %= length;
+        let (a, b, c) = (remaining[(index + length - 1) % length], remaining[index], remaining[(index + 1) % length]);
+        if failures >= length || is_ear(points, &remaining, a, b, c, winding) {
+            triangles.push([a as u32, b as u32, c as u32]);
+            remaining.remove(index);
+            failures = 0;
+        } else {
+            index += 1;
+            failures += 1;
+        }
+    }
+    triangles.push([remaining[0] as u32, remaining[1] as u32, remaining[2] as u32]);
+    triangles
+}
+
+fn is_ear(points: &[Vector], remaining: &[usize], a: usize, b: usize, c: usize, winding: f32) -> bool {
+    let (a, b, c) = (points[a], points[b], points[c]);
+    if (b - a).cross(c - b) * winding <= 0.0 {
+        return false;
+    }
+    !remaining.iter()
+        .map(|&index| points[index])
+        .filter(|&point| point != a && point != b && point != c)
+        .any(|point| {
+            (b - a).cross(point - a) * winding >= 0.0
+                && (c - b).cross(point - b) * winding >= 0.0
+                && (a - c).cross(point - c) * winding >= 0.0
+        })
+}
+
+// Find the direction each corner of a polygon moves in when the polygon grows, scaled so that
+// moving by it grows the polygon by one unit
+fn corner_offsets(points: &[Vector]) -> Vec<Vector> {
+    let count = points.len();
+    let area: f32 = (0..count).map(|i| points[i].cross(points[(i + 1) % count])).sum();
+    let outward = if area < 0.0 { 1.0 } else { -1.0 };
+    let normal = |from: Vector, to: Vector| {
+        let direction = (to - from).normalize();
+        Vector::new(-direction.y, direction.x) * outward
+    };
+    (0..count).map(|i| {
+        let previous = normal(points[(i + count - 1) % count], points[i]);
+        let next = normal(points[i], points[(i + 1) % count]);
+        let bisector = previous + next;
+        if bisector.len2() < 1e-6 {
+            // The polygon doubles back on itself here
+            return previous;
+        }
+        let bisector = bisector.normalize();
+        bisector / bisector.dot(previous).max(1e-3)
+    }).collect()
+}
+
+// Grow a polygon outward by a distance, or shrink it if the distance is negative
+pub(crate) fn offset_polygon(points: &[Vector], distance: f32) -> Vec<Vector> {
+    points.iter()
+        .zip(corner_offsets(points))
+        .map(|(&point, offset)| point + offset * distance)
+        .collect()
+}
+
+// Find the triangles that draw a line through the given points
+//
+// Each segment is drawn as its own quad, with the joins filling in the outside of the corners
+// between them. A closed line also joins its last point back to its first, and has no caps; it is
+// moved inside or outside the shape it outlines according to the stroke's alignment. Round caps
+// and joins are made of the given number of segments per full turn.
+pub(crate) fn stroke(points: &[Vector], closed: bool, stroke: Stroke, segments: usize) -> Geometry {
+    let mut geometry = Geometry::default();
+    // Repeated points have no direction, so they are skipped
+    let mut path: Vec<Vector> = Vec::with_capacity(points.len());
+    for &point in points.iter() {
+        if path.last().map_or(true, |&last| last != point) {
+            path.push(point);
+        }
+    }
+    if closed && path.len() > 2 && path[0] == path[path.len() - 1] {
+        path.pop();
+    }
+    let half = stroke.thickness / 2.0;
+    if closed && path.len() > 2 {
+        match stroke.alignment {
+            StrokeAlignment::Inside => path = offset_polygon(&path, -half),
+            StrokeAlignment::Center => (),
+            StrokeAlignment::Outside => path = offset_polygon(&path, half)
+        }
+        // Mitered outlines can be drawn as a ring between the grown and shrunk shape, which
+        // doesn't overlap itself at the corners
+        let offsets = corner_offsets(&path);
+        if stroke.join == LineJoin::Miter && offsets.iter().all(|offset| offset.len() <= stroke.miter_limit) {
+            for i in 0..path.len() {
+                let next = (i + 1) % path.len();
+                geometry.add_quad(path[i] + offsets[i] * half, path[next] + offsets[next] * half,
+                                  path[next] - offsets[next] * half, path[i] - offsets[i] * half);
+            }
+            return geometry;
+        }
+    }
+    if path.len() < 2 {
+        // A line with no length is just its caps
+        if let Some(&point) = path.first() {
+            match stroke.cap {
+                LineCap::Butt => (),
+                LineCap::Square => geometry.add_quad(
+                    point + Vector::new(-half, -half), point + Vector::new(half, -half),
+                    point + Vector::new(half, half), point + Vector::new(-half, half)),
+                LineCap::Round => geometry.add_arc(point, Vector::new(half, 0.0), 360.0, segments)
+            }
+        }
+        return geometry;
+    }
+    let lines = if closed { path.len() } else { path.len() - 1 };
+    for i in 0..lines {
+        let (mut start, mut end) = (path[i], path[(i + 1) % path.len()]);
+        let direction = (end - start).normalize();
+        let normal = Vector::new(-direction.y, direction.x) * half;
+        if !closed && stroke.cap == LineCap::Square {
+            if i == 0 {
+                start -= direction * half;
+            }
+            if i == lines - 1 {
+                end += direction * half;
+            }
+        }
+        geometry.add_quad(start + normal, end + normal, end - normal, start - normal);
+    }
+    let corners = if closed { 0..path.len() } else { 1..path.len() - 1 };
+    for i in corners {
+        let previous = path[(i + path.len() - 1) % path.len()];
+        let next = path[(i + 1) % path.len()];
+        add_join(&mut geometry, previous, path[i], next, half, stroke, segments);
+    }
+    if !closed && stroke.cap == LineCap::Round {
+        let last = path.len() - 1;
+        let start_direction = (path[1] - path[0]).normalize();
+        let end_direction = (path[last] - path[last - 1]).normalize();
+        geometry.add_arc(path[0], Vector::new(-start_direction.y, start_direction.x) * half, 180.0, segments);
+        geometry.add_arc(path[last], Vector::new(end_direction.y, -end_direction.x) * half, 180.0, segments);
+    }
+    geometry
+}
+
+fn add_join(geometry: &mut Geometry, previous: Vector, point: Vector, next: Vector, half: f32, stroke: Stroke,
+            segments: usize) {
+    let incoming = (point - previous).normalize();
+    let outgoing = (next - point).normalize();
+    let turn = incoming.cross(outgoing);
+    if turn.abs() < 1e-6 && incoming.dot(outgoing) > 0.0 {
+        // The segments continue straight on, so there is no gap to fill
+        return;
+    }
+    // The gap is on the outside of the turn, away from the side the line turns towards
+    let side = if turn > 0.0 { -half } else { half };
+    let first = Vector::new(-incoming.y, incoming.x) * side;
+    let second = Vector::new(-outgoing.y, outgoing.x) * side;
+    match stroke.join {
+        LineJoin::Bevel => geometry.add_triangle(point, point + first, point + second),
+        LineJoin::Round => {
+            let sweep = first.cross(second).atan2(first.dot(second)).to_degrees();
+            geometry.add_arc(point, first, sweep, segments);
+        }
+        LineJoin::Miter => {
+            let miter = first + second;
+            let cosine = if miter.len2() > 0.0 { miter.normalize().dot(first) / half } else { 0.0 };
+            if cosine <= 0.0 || 1.0 / cosine > stroke.miter_limit {
+                geometry.add_triangle(point, point + first, point + second);
+            } else {
+                let tip = point + miter.normalize() * (half / cosine);
+                geometry.add_quad(point, point + first, tip, point + second);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(points: &[Vector], triangles: &[[u32; 3]]) -> f32 {
+        triangles.iter().map(|triangle| {
+            let (a, b, c) = (points[triangle[0] as usize], points[triangle[1] as usize], points[triangle[2] as usize]);
+            (b - a).cross(c - a).abs() / 2.0
+        }).sum()
+    }
+
+    #[test]
+    fn convex() {
+        let square = [Vector::new(0, 0), Vector::new(10, 0), Vector::new(10, 10), Vector::new(0, 10)];
+        let triangles = triangulate(&square);
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(area(&square, &triangles), 100.0);
+    }
+
+    #[test]
+    fn concave() {
+        let l_shape = [
+            Vector::new(0, 0), Vector::new(20, 0), Vector::new(20, 10),
+            Vector::new(10, 10), Vector::new(10, 20), Vector::new(0, 20)
+        ];
+        let triangles = triangulate(&l_shape);
+        assert_eq!(triangles.len(), 4);
+        assert_eq!(area(&l_shape, &triangles), 300.0);
+        let reversed: Vec<Vector> = l_shape.iter().rev().cloned().collect();
+        assert_eq!(area(&reversed, &triangulate(&reversed)), 300.0);
+    }
+
+    #[test]
+    fn line_caps() {
+        let line = [Vector::new(0, 0), Vector::new(10, 0)];
+        let butt = stroke(&line, false, Stroke::new(2), 24);
+        assert_eq!(area(&butt.points, &butt.triangles), 20.0);
+        let square = stroke(&line, false, Stroke::new(2).with_cap(LineCap::Square), 24);
+        assert_eq!(area(&square.points, &square.triangles), 24.0);
+        let round = stroke(&line, false, Stroke::new(2).with_cap(LineCap::Round), 24);
+        assert!(area(&round.points, &round.triangles) > 22.5);
+        assert!(area(&round.points, &round.triangles) < 20.0 + ::std::f32::consts::PI);
+        let wide = stroke(&line, false, Stroke::new(200).with_cap(LineCap::Round), circle_segments(100.0));
+        let thin = stroke(&line, false, Stroke::new(1).with_cap(LineCap::Round), circle_segments(0.5));
+        assert!(wide.points.len() > round.points.len());
+        assert!(thin.points.len() < round.points.len());
+    }
+
+    #[test]
+    fn joins() {
+        let corner = [Vector::new(0, 0), Vector::new(10, 0), Vector::new(10, 10)];
+        let miter = stroke(&corner, false, Stroke::new(2), 24);
+        assert_eq!(area(&miter.points, &miter.triangles), 41.0);
+        let bevel = stroke(&corner, false, Stroke::new(2).with_join(LineJoin::Bevel), 24);
+        assert_eq!(area(&bevel.points, &bevel.triangles), 40.5);
+    }
+
+    #[test]
+    fn adaptive_segments() {
+        assert_eq!(circle_segments(0.1), 6);
+        assert!(circle_segments(5.0) < circle_segments(50.0));
+        assert!(circle_segments(50.0) < circle_segments(500.0));
+        assert_eq!(circle_segments(1e9), 512);
+        let segments = circle_segments(100.0);
+        let circle = ellipse_points(Vector::zero(), Vector::new(100, 100), 0.0, 360.0, segments);
+        assert_eq!(circle.len(), segments);
+        let edge_middle = (circle[0] + circle[1]) / 2;
+        assert!(100.0 - edge_middle.len() <= 0.25);
+    }
+
+    #[test]
+    fn sectors() {
+        let quarter = ellipse_points(Vector::zero(), Vector::new(10, 20), 0.0, 90.0, 4);
+        assert_eq!(quarter.len(), 5);
+        assert_eq!(quarter[0], Vector::new(10, 0));
+        assert_eq!(quarter[4], Vector::new(0, 20));
+        let pie = fan(Vector::zero(), &quarter, false);
+        assert_eq!(pie.triangles.len(), 4);
+        let square = [Vector::new(-1, -1), Vector::new(1, -1), Vector::new(1, 1), Vector::new(-1, 1)];
+        let filled = fan(Vector::zero(), &square, true);
+        assert_eq!(area(&filled.points, &filled.triangles), 4.0);
+    }
+
+    #[test]
+    fn outlines() {
+        let square = [Vector::new(0, 0), Vector::new(10, 0), Vector::new(10, 10), Vector::new(0, 10)];
+        assert_eq!(offset_polygon(&square, -1.0), vec![Vector::new(1, 1), Vector::new(9, 1), Vector::new(9, 9), Vector::new(1, 9)]);
+        let center = stroke(&square, true, Stroke::new(2), 24);
+        assert_eq!(center.triangles.len(), 8);
+        assert_eq!(area(&center.points, &center.triangles), 80.0);
+        let inside = stroke(&square, true, Stroke::new(2).with_alignment(StrokeAlignment::Inside), 24);
+        assert_eq!(area(&inside.points, &inside.triangles), 64.0);
+        assert!(inside.points.iter().all(|point| point.x >= 0.0 && point.x <= 10.0));
+        let reversed: Vec<Vector> = square.iter().rev().cloned().collect();
+        let outside = stroke(&reversed, true, Stroke::new(2).with_alignment(StrokeAlignment::Outside), 24);
+        assert_eq!(area(&outside.points, &outside.triangles), 96.0);
+    }
+}
@@ -2,12 +2,16 @@ use ffi::gl;
 #[cfg(not(target_arch="wasm32"))] use glutin;
 use geom::{ Rectangle, Transform, Vector};
 #[cfg(not(target_arch="wasm32"))] use glutin::{EventsLoop, GlContext};
-use graphics::{Backend, BlendMode, Color, Drawable, GpuTriangle, ResizeStrategy, Vertex, View};
-use input::{ButtonState, Event, Gamepad, GamepadProvider, Keyboard, Mouse};
+use graphics::{Backend, BlendMode, Color, Drawable, FrameStats, GpuTriangle, Mesh, Pixels, ResizeStrategy, Shader, StatsCollector, Vertex, View};
+use input::{ButtonState, Event, Gamepad, GamepadProvider, InputLog, Keyboard, Mouse, Recorder, Replayer};
+#[cfg(all(feature="capture", not(target_arch="wasm32")))]
+use graphics::{Capture, CaptureSettings, capture::FrameCapture};
+use scheduler::Scheduler;
+use timer::{check_tick_rate, current_time};
 
 /// The way the images should change when drawn at a scale
 #[repr(u32)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub enum ImageScaleStrategy {
     /// The image should attempt to preserve each pixel as accurately as possible
     Pixelate = gl::NEAREST,
@@ -28,7 +32,9 @@ pub struct WindowBuilder {
     max_size: Option<Vector>,
     resize: ResizeStrategy,
     scale: ImageScaleStrategy,
-    fullscreen: bool
+    fullscreen: bool,
+    update_rate: f64,
+    max_updates: u32
 }
 
 impl WindowBuilder {
@@ -45,7 +51,9 @@ impl WindowBuilder {
             max_size: None,
             resize: ResizeStrategy::Fit,
             scale: ImageScaleStrategy::Pixelate,
-            fullscreen: false
+            fullscreen: false,
+            update_rate: 1000. / 60.,
+            max_updates: 10
         }
     }
    
@@ -87,7 +95,7 @@ impl WindowBuilder {
         }
     }
 
-    ///Set the strategy for scaling images
+    ///Set the strategy for scaling images, which each Image can override with `Image::set_scale_strategy`
     pub fn with_scaling_strategy(self, scale: ImageScaleStrategy) -> WindowBuilder {
         WindowBuilder {
             scale,
@@ -105,6 +113,29 @@ impl WindowBuilder {
         }
     }
 
+    ///Set the number of milliseconds between each update (defaults to 1000 / 60)
+    ///
+    ///Updates happen at this fixed rate no matter how often the window is drawn. The rate is
+    ///milliseconds per update rather than updates per second, and must be positive.
+    pub fn with_update_rate(self, update_rate: f64) -> WindowBuilder {
+        check_tick_rate(update_rate);
+        WindowBuilder {
+            update_rate,
+            ..self
+        }
+    }
+
+    ///Set the maximum number of updates that can run between two draws (defaults to 10)
+    ///
+    ///If the application falls further behind than this, the remaining time is dropped rather
+    ///than spent catching up
+    pub fn with_max_updates(self, max_updates: u32) -> WindowBuilder {
+        WindowBuilder {
+            max_updates,
+            ..self
+        }
+    }
+
     #[cfg(not(target_arch="wasm32"))]
     pub(crate) fn build(self) -> (Window, EventsLoop) {
         let mut actual_width = self.width;
@@ -129,6 +160,7 @@ impl WindowBuilder {
         let window = window.with_dimensions(actual_width, actual_height);
         let context = glutin::ContextBuilder::new().with_vsync(true);
         let gl_window = glutin::GlWindow::new(window, context, &events).unwrap();
+        gl::set_headless(false);
         unsafe {
             gl_window.make_current().unwrap();
             gl::load_with(|symbol| gl_window.get_proc_address(symbol) as *const _);
@@ -139,7 +171,7 @@ impl WindowBuilder {
         let screen_region = self.resize.resize(Vector::new(self.width, self.height), Vector::new(actual_width, actual_height)); 
         let view = View::new(Rectangle::newv_sized(screen_region.size()));
         (Window {
-            gl_window,
+            gl_window: Some(gl_window),
             gamepads: Vec::new(),
             gamepad_buffer: Vec::new(),
             provider: GamepadProvider::new(),
@@ -149,12 +181,61 @@ impl WindowBuilder {
             keyboard: Keyboard { keys: [ButtonState::NotPressed; 256] },
             mouse: Mouse { pos: Vector::zero(), buttons: [ButtonState::NotPressed; 3], wheel: Vector::zero() },
             view,
-            backend: Backend::new(self.scale as u32),
+            backend: Backend::new(self.scale),
             vertices: Vec::new(),
-            triangles: Vec::new()
+            triangles: Vec::new(),
+            update_rate: self.update_rate,
+            max_updates: self.max_updates,
+            alpha: 0.0,
+            headless: false,
+            drawn_vertices: Vec::new(),
+            drawn_triangles: Vec::new(),
+            tick: 0,
+            recorder: None,
+            replayer: None,
+            #[cfg(all(feature="capture", not(target_arch="wasm32")))]
+            capture: None,
+            stats: StatsCollector::new(current_time()),
+            scheduler: Scheduler::new()
         }, events)
     }
 
+    // Build a Window with no display or graphics context, which only records what is drawn to it
+    #[cfg(not(target_arch="wasm32"))]
+    pub(crate) fn build_headless(self) -> Window {
+        gl::set_headless(true);
+        let screen_region = self.resize.resize(Vector::new(self.width, self.height), Vector::new(self.width, self.height));
+        let view = View::new(Rectangle::newv_sized(screen_region.size()));
+        Window {
+            gl_window: None,
+            gamepads: Vec::new(),
+            gamepad_buffer: Vec::new(),
+            provider: GamepadProvider::headless(),
+            resize: self.resize,
+            screen_region,
+            scale_factor: 1.0,
+            keyboard: Keyboard { keys: [ButtonState::NotPressed; 256] },
+            mouse: Mouse { pos: Vector::zero(), buttons: [ButtonState::NotPressed; 3], wheel: Vector::zero() },
+            view,
+            backend: Backend::new(self.scale),
+            vertices: Vec::new(),
+            triangles: Vec::new(),
+            update_rate: self.update_rate,
+            max_updates: self.max_updates,
+            alpha: 0.0,
+            headless: true,
+            drawn_vertices: Vec::new(),
+            drawn_triangles: Vec::new(),
+            tick: 0,
+            recorder: None,
+            replayer: None,
+            #[cfg(all(feature="capture", not(target_arch="wasm32")))]
+            capture: None,
+            stats: StatsCollector::new(current_time()),
+            scheduler: Scheduler::new()
+        }
+    }
+
     #[cfg(target_arch="wasm32")]
     pub(crate) fn build(self) -> Window {
         let mut actual_width = self.width;
@@ -181,17 +262,31 @@ impl WindowBuilder {
             keyboard: Keyboard { keys: [ButtonState::NotPressed; 256] },
             mouse: Mouse { pos: Vector::zero(), buttons: [ButtonState::NotPressed; 3], wheel: Vector::zero() },
             view,
-            backend: Backend::new(self.scale as u32),
+            backend: Backend::new(self.scale),
             vertices: Vec::new(),
-            triangles: Vec::new()
+            triangles: Vec::new(),
+            update_rate: self.update_rate,
+            max_updates: self.max_updates,
+            alpha: 0.0,
+            headless: false,
+            drawn_vertices: Vec::new(),
+            drawn_triangles: Vec::new(),
+            tick: 0,
+            recorder: None,
+            replayer: None,
+            stats: StatsCollector::new(current_time()),
+            scheduler: Scheduler::new()
         }
     }
 }
 
 ///The window currently in use
 pub struct Window {
+    // A capture reads its last frames when it is dropped, so it has to go before the context
+    #[cfg(all(feature="capture", not(target_arch="wasm32")))]
+    capture: Option<FrameCapture>,
     #[cfg(not(target_arch="wasm32"))]
-    pub(crate) gl_window: glutin::GlWindow,
+    pub(crate) gl_window: Option<glutin::GlWindow>,
     provider: GamepadProvider,
     gamepads: Vec<Gamepad>,
     gamepad_buffer: Vec<Gamepad>, //used as a temporary buffer for storing new gamepads
@@ -203,11 +298,25 @@ pub struct Window {
     view: View,
     pub(crate) backend: Backend,
     vertices: Vec<Vertex>,
-    triangles: Vec<GpuTriangle>
+    triangles: Vec<GpuTriangle>,
+    pub(crate) update_rate: f64,
+    pub(crate) max_updates: u32,
+    pub(crate) alpha: f32,
+    headless: bool,
+    pub(crate) drawn_vertices: Vec<Vertex>,
+    pub(crate) drawn_triangles: Vec<GpuTriangle>,
+    pub(crate) tick: u64,
+    recorder: Option<Recorder>,
+    replayer: Option<Replayer>,
+    pub(crate) stats: StatsCollector,
+    pub(crate) scheduler: Scheduler
 }
 
 impl Window {
     pub(crate) fn process_event(&mut self, event: &Event) {
+        if let Some(ref mut recorder) = self.recorder {
+            recorder.record(self.tick, *event);
+        }
         match event {
             &Event::Key(key, state) => self.keyboard.process_event(key as usize, state),
             &Event::MouseMoved(pos) => self.mouse = Mouse { 
@@ -216,30 +325,155 @@ impl Window {
             },
             &Event::MouseWheel(wheel) => self.mouse = Mouse { wheel, ..self.mouse },
             &Event::MouseButton(button, state) => self.mouse.process_button(button, state),
+            &Event::GamepadConnected(id) => {
+                self.gamepad(id);
+            }
+            &Event::GamepadDisconnected(id) => self.gamepads.retain(|gamepad| gamepad.id() != id),
+            &Event::GamepadButton(id, button, state) => self.gamepad(id).set_button(button, state),
+            &Event::GamepadAxis(id, axis, value) => self.gamepad(id).set_axis(axis, value),
             _ => ()
         }
     }
 
+    // Find a gamepad by its id, connecting it if it isn't already
+    fn gamepad(&mut self, id: u32) -> &mut Gamepad {
+        let index = match self.gamepads.iter().position(|gamepad| gamepad.id() == id) {
+            Some(index) => index,
+            None => {
+                self.gamepads.push(Gamepad::new(id));
+                self.gamepads.len() - 1
+            }
+        };
+        &mut self.gamepads[index]
+    }
+
+    // Poll the gamepads, adding events for whatever changed since the last poll
+    //
+    // The gamepads only change through their events, so recorded and replayed gamepad input
+    // affects them the same way as live input. While a replay runs, the live gamepads are ignored.
     pub(crate) fn update_gamepads(&mut self, events: &mut Vec<Event>) {
+        if self.is_replaying() {
+            return;
+        }
         self.provider.provide_gamepads(&mut self.gamepad_buffer);
-        let (mut i, mut j) = (0, 0);
-        while i < self.gamepads.len() && j < self.gamepad_buffer.len() {
-            if self.gamepads[i].id() == self.gamepad_buffer[j].id() {
-                self.gamepad_buffer[j].set_previous(&self.gamepads[i], events);
-                i += 1;
-                j += 1;
-            } else if self.gamepads[i].id() > self.gamepad_buffer[j].id() {
-                events.push(Event::GamepadDisconnected(self.gamepad_buffer[j].id()));
-                j += 1;
-            } else {
-                events.push(Event::GamepadConnected(self.gamepads[i].id()));
-                i += 1;
+        for gamepad in self.gamepads.iter() {
+            if !self.gamepad_buffer.iter().any(|current| current.id() == gamepad.id()) {
+                events.push(Event::GamepadDisconnected(gamepad.id()));
             }
         }
-        self.gamepads.clear();
-        self.gamepads.append(&mut self.gamepad_buffer);
+        for current in self.gamepad_buffer.iter() {
+            match self.gamepads.iter().find(|gamepad| gamepad.id() == current.id()) {
+                Some(previous) => current.changes_from(previous, events),
+                None => {
+                    events.push(Event::GamepadConnected(current.id()));
+                    current.changes_from(&Gamepad::new(current.id()), events);
+                }
+            }
+        }
+        self.gamepad_buffer.clear();
     }
     
+    // Check if an event from the user should be delivered, which it shouldn't during a replay
+    pub(crate) fn accepts_input(&self, event: &Event) -> bool {
+        !self.is_replaying() || *event == Event::Closed
+    }
+
+    // Take the next replayed event that is due before the coming update
+    pub(crate) fn next_replayed_event(&mut self) -> Option<Event> {
+        let (event, finished) = match self.replayer {
+            Some(ref mut replayer) => (replayer.next(self.tick), replayer.is_finished()),
+            None => return None
+        };
+        if finished {
+            self.replayer = None;
+        }
+        event
+    }
+
+    ///Get the number of updates that have run since the Window was created
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    ///Start recording every input event sent to the Window
+    ///
+    ///Events are tagged with the number of updates between the start of the recording and
+    ///their arrival. If a recording is already running, it is discarded.
+    pub fn start_recording(&mut self) {
+        self.recorder = Some(Recorder::new(self.tick));
+    }
+
+    ///Stop recording input events, returning the log of the recording if there was one
+    pub fn stop_recording(&mut self) -> Option<InputLog> {
+        self.recorder.take().map(Recorder::finish)
+    }
+
+    ///Check if the Window is recording input events
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    ///Replay a log of input events in place of the input from the user
+    ///
+    ///The keyboard, mouse and gamepads are reset, and the events are sent on the same ticks
+    ///relative to the start of the replay as they were relative to the start of the recording.
+    ///Until the log runs out, events from the user are ignored, except for `Event::Closed`, and
+    ///the live gamepads aren't polled.
+    pub fn replay(&mut self, log: InputLog) {
+        self.keyboard = Keyboard { keys: [ButtonState::NotPressed; 256] };
+        self.mouse = Mouse { pos: Vector::zero(), buttons: [ButtonState::NotPressed; 3], wheel: Vector::zero() };
+        self.gamepads.clear();
+        self.replayer = Some(Replayer::new(self.tick, log));
+    }
+
+    ///Stop a replay early, returning control to the user
+    pub fn stop_replay(&mut self) {
+        self.replayer = None;
+    }
+
+    ///Check if the Window is replaying a log of input events
+    pub fn is_replaying(&self) -> bool {
+        self.replayer.is_some()
+    }
+
+    ///Start capturing the presented frames into an animated image
+    ///
+    ///Frames are captured when `present` is called, until the capture's duration has passed or
+    ///`stop_capture` is called. If a capture is already running, it is stopped and saved first.
+    #[cfg(all(feature="capture", not(target_arch="wasm32")))]
+    pub fn start_capture(&mut self, settings: CaptureSettings) -> Capture {
+        let (capture, future) = FrameCapture::start(settings, current_time());
+        self.capture = Some(capture);
+        future
+    }
+
+    ///Stop capturing frames early, which saves the ones captured so far
+    #[cfg(all(feature="capture", not(target_arch="wasm32")))]
+    pub fn stop_capture(&mut self) {
+        self.capture = None;
+    }
+
+    ///Check if the Window is capturing its presented frames
+    #[cfg(all(feature="capture", not(target_arch="wasm32")))]
+    pub fn is_capturing(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    // Start reading back the frame about to be presented for the capture, if it wants it
+    #[cfg(all(feature="capture", not(target_arch="wasm32")))]
+    fn capture_frame(&mut self) {
+        let now = current_time();
+        if self.capture.as_ref().map(|capture| capture.is_over(now)).unwrap_or(false) {
+            self.capture = None;
+        }
+        let region = self.screen_region;
+        if let Some(ref mut capture) = self.capture {
+            if capture.wants_frame() {
+                capture.read(region.x as i32, region.y as i32, region.width as u32, region.height as u32, now);
+            }
+        }
+    }
+
     ///Transition temporary input states (Pressed, Released) into sustained ones (Held, NotPressed)
     pub fn clear_temporary_states(&mut self) {
         self.keyboard.clear_temporary_states();
@@ -252,10 +486,16 @@ impl Window {
     ///Handle the available size for the window changing
     pub(crate) fn adjust_size(&mut self, available: Vector) {
         self.screen_region = self.resize.resize(self.screen_region.size(), available);
+        if self.headless {
+            return;
+        }
         unsafe { gl::Viewport(self.screen_region.x as i32, self.screen_region.y as i32, 
                               self.screen_region.width as i32, self.screen_region.height as i32); }
-        #[cfg(not(target_arch="wasm32"))]
-        self.gl_window.resize(self.screen_region.width as u32, self.screen_region.height as u32);
+        #[cfg(not(target_arch="wasm32"))] {
+            if let Some(ref gl_window) = self.gl_window {
+                gl_window.resize(self.screen_region.width as u32, self.screen_region.height as u32);
+            }
+        }
     }
 
 
@@ -269,6 +509,53 @@ impl Window {
         self.view = view;
     }
 
+    ///Get the number of milliseconds between each update
+    pub fn update_rate(&self) -> f64 {
+        self.update_rate
+    }
+
+    ///Set the number of milliseconds between each update, which must be positive
+    pub fn set_update_rate(&mut self, update_rate: f64) {
+        check_tick_rate(update_rate);
+        self.update_rate = update_rate;
+    }
+
+    ///Get the maximum number of updates that can run between two draws
+    pub fn max_updates(&self) -> u32 {
+        self.max_updates
+    }
+
+    ///Set the maximum number of updates that can run between two draws
+    pub fn set_max_updates(&mut self, max_updates: u32) {
+        self.max_updates = max_updates;
+    }
+
+    ///How far the current draw is between the last update and the next one, from 0 to 1
+    ///
+    ///Draws happen more often than updates, so positions can be interpolated using this value
+    ///to smooth out motion between updates
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    ///Get the statistics gathered over the last complete frame
+    ///
+    ///While the current frame is being drawn, this still returns the previous frame's statistics
+    pub fn stats(&self) -> FrameStats {
+        self.stats.last()
+    }
+
+    // Wrap up the statistics for the frame in progress
+    pub(crate) fn finish_frame(&mut self) {
+        let (draw_calls, texture_switches, draw_calls_saved) = self.backend.take_counts();
+        self.stats.finish(current_time(), draw_calls, texture_switches, draw_calls_saved);
+    }
+
+    ///Get the scheduler that runs tasks as the window ticks
+    pub fn scheduler(&mut self) -> &mut Scheduler {
+        &mut self.scheduler
+    }
+
     ///Get the resize strategy used by the window
     pub fn resize_strategy(&self) -> ResizeStrategy {
         self.resize
@@ -324,7 +611,9 @@ impl Window {
 
     #[cfg(not(target_arch="wasm32"))]
     fn set_title_impl(&self, title: &str) {
-        self.gl_window.set_title(title);
+        if let Some(ref gl_window) = self.gl_window {
+            gl_window.set_title(title);
+        }
     }
     
     #[cfg(target_arch="wasm32")]
@@ -348,8 +637,16 @@ impl Window {
     /// Flush changes and also present the changes to the window
     pub fn present(&mut self) {
         self.flush();
-        #[cfg(not(target_arch="wasm32"))]
-        self.gl_window.swap_buffers().unwrap();
+        let start = current_time();
+        #[cfg(all(feature="capture", not(target_arch="wasm32")))]
+        self.capture_frame();
+        #[cfg(not(target_arch="wasm32"))] {
+            if let Some(ref gl_window) = self.gl_window {
+                gl_window.swap_buffers().unwrap();
+            }
+        }
+        // Swapping waits for vsync, which shouldn't count as time spent drawing
+        self.stats.record_present(current_time() - start);
     }
 
     /// Flush the current buffered draw calls
@@ -361,11 +658,22 @@ impl Window {
     /// the fewer times your application needs to flush the faster it will run.
     pub fn flush(&mut self) {
         self.triangles.sort();
+        self.stats.record_flush(self.vertices.len(), self.triangles.len());
         self.backend.draw(self.vertices.as_slice(), self.triangles.as_slice());
         self.vertices.clear();
         self.triangles.clear();
     }
 
+    /// Capture the frame drawn so far
+    ///
+    /// Everything drawn is flushed first. Call it after drawing and before `present`, because
+    /// presenting the frame leaves the next one to be drawn from scratch.
+    pub fn screenshot(&mut self) -> Pixels {
+        self.flush();
+        let region = self.screen_region;
+        Pixels::read(0, region.x as i32, region.y as i32, region.width as u32, region.height as u32, true)
+    }
+
     /// Set the blend mode for the window
     ///
     /// This will flush all of the drawn items to the screen and 
@@ -383,6 +691,32 @@ impl Window {
         self.backend.reset_blend_mode();
     }
 
+    /// Set the shader that items are drawn with
+    ///
+    /// This will flush all of the drawn items to the screen with the previous shader, so only
+    /// the items drawn after this use the new one.
+    pub fn set_shader(&mut self, shader: &Shader) {
+        self.flush();
+        self.backend.set_shader(Some(shader.clone()));
+    }
+
+    /// Go back to drawing with the default shader
+    ///
+    /// This will flush all of the drawn items to the screen
+    pub fn reset_shader(&mut self) {
+        self.flush();
+        self.backend.set_shader(None);
+    }
+
+    /// Draw a batch of items with a shader, and then go back to the shader that was set before
+    pub fn draw_with_shader<F>(&mut self, shader: &Shader, func: F) where F: FnOnce(&mut Window) {
+        let previous = self.backend.shader();
+        self.set_shader(shader);
+        func(self);
+        self.flush();
+        self.backend.set_shader(previous);
+    }
+
     /// Draw a single object to the screen
     ///
     /// It will not appear until Window::flush is called
@@ -390,6 +724,24 @@ impl Window {
         item.draw(self);
     }
 
+    /// Draw a mesh with a transform and a color to tint it with
+    ///
+    /// This will flush all of the drawn items to the screen first, so the mesh appears on top of
+    /// them. The transform maps the mesh's vertices into the current view.
+    pub fn draw_mesh(&mut self, mesh: &Mesh, transform: Transform, color: Color) {
+        if self.headless {
+            let vertices = mesh.vertices().iter().map(|vertex| Vertex {
+                pos: transform * vertex.pos,
+                col: vertex.col.multiply(color),
+                ..*vertex
+            });
+            return self.record_vertices(vertices, mesh.triangles().iter().cloned());
+        }
+        self.flush();
+        let transform = self.view.opengl * transform;
+        self.backend.draw_mesh(mesh, transform, color);
+    }
+
     /// Add vertices directly to the list without using a Drawable
     ///
     /// Each vertex has a position in terms of the current view. The indices
@@ -397,6 +749,9 @@ impl Window {
     /// the index must be at least 0 and at most the number of vertices.
     /// Other index values will have undefined behavior
     pub fn add_vertices<V, T>(&mut self, vertices: V, triangles: T) where V: Iterator<Item = Vertex>, T: Iterator<Item = GpuTriangle> {
+        if self.headless {
+            return self.record_vertices(vertices, triangles);
+        }
         let offset = self.vertices.len() as u32;
         self.triangles.extend(triangles.map(|t| GpuTriangle {
             indices: [t.indices[0] + offset, t.indices[1] + offset, t.indices[2] + offset],
@@ -409,6 +764,16 @@ impl Window {
         }));
     }
 
+    // Headless windows keep what is drawn to them in terms of the view, instead of sending it to the GPU
+    fn record_vertices<V, T>(&mut self, vertices: V, triangles: T) where V: Iterator<Item = Vertex>, T: Iterator<Item = GpuTriangle> {
+        let offset = self.drawn_vertices.len() as u32;
+        self.drawn_triangles.extend(triangles.map(|t| GpuTriangle {
+            indices: [t.indices[0] + offset, t.indices[1] + offset, t.indices[2] + offset],
+            ..t
+        }));
+        self.drawn_vertices.extend(vertices);
+    }
+
     /// Get a reference to the connected gamepads
     pub fn gamepads(&self) -> &Vec<Gamepad> {
         &self.gamepads
@@ -0,0 +1,228 @@
+use graphics::{GpuTriangle, Vertex, Window, WindowBuilder};
+use input::Event;
+use state::{Application, State, Transition};
+use timer::current_time;
+use Result;
+
+/// A way to run a State without a display or a graphics context, for testing
+///
+/// The Window is built without opening anything, so Images and Surfaces have no GPU data, and
+/// instead of drawing to the screen the Window records what is drawn to it. Time doesn't pass on
+/// its own: each frame runs exactly one update followed by one draw, so tests are deterministic.
+///
+/// Only one headless Window should be in use on a thread at a time, and it shouldn't share the
+/// thread with a regular one.
+pub struct Headless {
+    app: Application
+}
+
+impl Headless {
+    /// Create a State and start running it in a headless Window
+    pub fn new<T: 'static + State>(window: WindowBuilder) -> Result<Headless> {
+        let window = window.build_headless();
+        let state = Box::new(T::new()?);
+        Ok(Headless {
+            app: Application::new(state, window)?
+        })
+    }
+
+    /// Send an event to the Window and the running State, as though the user caused it
+    ///
+    /// The Keyboard and Mouse are updated right away; Pressed and Released states last until
+    /// the end of the next update, like they would in a real game loop.
+    pub fn event(&mut self, event: Event) -> Result<()> {
+        self.app.input(&event)
+    }
+
+    /// Run a number of frames, each made of one update and then one draw
+    ///
+    /// Stops early if the States quit. Only the last frame's draw is kept by `vertices` and
+    /// `triangles`.
+    pub fn step(&mut self, frames: u32) -> Result<()> {
+        for _ in 0..frames {
+            if !self.is_running() {
+                break;
+            }
+            let start = current_time();
+            self.app.update()?;
+            self.app.window.stats.record_update(current_time() - start);
+            self.app.window.clear_temporary_states();
+            self.app.window.alpha = 0.0;
+            self.app.window.drawn_vertices.clear();
+            self.app.window.drawn_triangles.clear();
+            let start = current_time();
+            self.app.draw()?;
+            self.app.window.stats.record_draw(current_time() - start);
+            self.app.window.finish_frame();
+        }
+        Ok(())
+    }
+
+    /// Check if there are any States left running
+    pub fn is_running(&self) -> bool {
+        self.app.is_running()
+    }
+
+    /// Exit every running State, as though the window was closed
+    pub fn quit(&mut self) -> Result<()> {
+        self.app.transition(Transition::Quit)
+    }
+
+    /// Get a reference to the Window, to inspect its Keyboard, Mouse or View
+    pub fn window(&self) -> &Window {
+        &self.app.window
+    }
+
+    /// Get a mutable reference to the Window
+    pub fn window_mut(&mut self) -> &mut Window {
+        &mut self.app.window
+    }
+
+    /// The vertices drawn in the last frame, positioned in terms of the View they were drawn with
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.app.window.drawn_vertices
+    }
+
+    /// The triangles drawn in the last frame, which index into `vertices`
+    pub fn triangles(&self) -> &[GpuTriangle] {
+        &self.app.window.drawn_triangles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geom::{Rectangle, Vector};
+    use graphics::{Color, Draw};
+    use input::{ButtonState, GamepadAxis, GamepadButton, Key, MouseButton};
+
+    struct Player {
+        pos: Vector
+    }
+
+    impl State for Player {
+        fn new() -> Result<Player> {
+            Ok(Player { pos: Vector::zero() })
+        }
+
+        fn update(&mut self, window: &mut Window) -> Result<Transition> {
+            if window.keyboard()[Key::Right].is_down() {
+                self.pos.x += 1.0;
+            }
+            Ok(if window.keyboard()[Key::Escape].is_down() { Transition::Quit } else { Transition::None })
+        }
+
+        fn draw(&mut self, window: &mut Window) -> Result<()> {
+            window.clear(Color::black());
+            window.draw(&Draw::rectangle(Rectangle::newv(self.pos, Vector::new(10, 10))));
+            window.present();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn input() {
+        let mut game = Headless::new::<Player>(WindowBuilder::new("", 800, 600)).unwrap();
+        game.event(Event::Key(Key::Right, ButtonState::Pressed)).unwrap();
+        assert_eq!(game.window().keyboard()[Key::Right], ButtonState::Pressed);
+        game.step(3).unwrap();
+        assert_eq!(game.window().keyboard()[Key::Right], ButtonState::Held);
+        game.event(Event::MouseMoved(Vector::new(40, 30))).unwrap();
+        game.event(Event::MouseButton(MouseButton::Left, ButtonState::Pressed)).unwrap();
+        assert_eq!(game.window().mouse().pos(), Vector::new(40, 30));
+        assert_eq!(game.window().mouse()[MouseButton::Left], ButtonState::Pressed);
+    }
+
+    #[test]
+    fn drawing() {
+        let mut game = Headless::new::<Player>(WindowBuilder::new("", 800, 600)).unwrap();
+        game.event(Event::Key(Key::Right, ButtonState::Pressed)).unwrap();
+        game.step(5).unwrap();
+        assert_eq!(game.vertices().len(), 4);
+        assert_eq!(game.triangles().len(), 2);
+        assert_eq!(game.vertices()[0].pos, Vector::new(5, 0));
+    }
+
+    #[test]
+    fn quitting() {
+        let mut game = Headless::new::<Player>(WindowBuilder::new("", 800, 600)).unwrap();
+        game.step(2).unwrap();
+        assert!(game.is_running());
+        game.event(Event::Key(Key::Escape, ButtonState::Pressed)).unwrap();
+        game.step(10).unwrap();
+        assert!(!game.is_running());
+    }
+
+    #[test]
+    fn replay() {
+        let mut game = Headless::new::<Player>(WindowBuilder::new("", 800, 600)).unwrap();
+        game.window_mut().start_recording();
+        game.step(2).unwrap();
+        game.event(Event::Key(Key::Right, ButtonState::Pressed)).unwrap();
+        game.step(4).unwrap();
+        game.event(Event::Key(Key::Right, ButtonState::Released)).unwrap();
+        game.step(3).unwrap();
+        let log = game.window_mut().stop_recording().unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log.events()[0].tick, 2);
+        let expected = game.vertices()[0].pos;
+        let mut replay = Headless::new::<Player>(WindowBuilder::new("", 800, 600)).unwrap();
+        replay.window_mut().replay(log);
+        replay.event(Event::Key(Key::Left, ButtonState::Pressed)).unwrap();
+        assert_eq!(replay.window().keyboard()[Key::Left], ButtonState::NotPressed);
+        replay.step(9).unwrap();
+        assert_eq!(replay.vertices()[0].pos, expected);
+        assert!(!replay.window().is_replaying());
+    }
+
+    // Moves with the left stick of the first gamepad, and jumps while its bottom face button is down
+    struct Pad {
+        pos: Vector
+    }
+
+    impl State for Pad {
+        fn new() -> Result<Pad> {
+            Ok(Pad { pos: Vector::zero() })
+        }
+
+        fn update(&mut self, window: &mut Window) -> Result<Transition> {
+            if let Some(gamepad) = window.gamepads().first() {
+                self.pos.x += gamepad[GamepadAxis::LeftStickX];
+                if gamepad[GamepadButton::FaceDown].is_down() {
+                    self.pos.y -= 1.0;
+                }
+            }
+            Ok(Transition::None)
+        }
+
+        fn draw(&mut self, window: &mut Window) -> Result<()> {
+            window.draw(&Draw::rectangle(Rectangle::newv(self.pos, Vector::new(10, 10))));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn gamepad_replay() {
+        let mut game = Headless::new::<Pad>(WindowBuilder::new("", 800, 600)).unwrap();
+        game.window_mut().start_recording();
+        game.event(Event::GamepadConnected(3)).unwrap();
+        game.event(Event::GamepadAxis(3, GamepadAxis::LeftStickX, 0.5)).unwrap();
+        game.step(4).unwrap();
+        game.event(Event::GamepadButton(3, GamepadButton::FaceDown, ButtonState::Pressed)).unwrap();
+        game.step(2).unwrap();
+        assert_eq!(game.window().gamepads()[0][GamepadButton::FaceDown], ButtonState::Held);
+        game.event(Event::GamepadButton(3, GamepadButton::FaceDown, ButtonState::Released)).unwrap();
+        game.event(Event::GamepadAxis(3, GamepadAxis::LeftStickX, 0.0)).unwrap();
+        game.step(3).unwrap();
+        let log = game.window_mut().stop_recording().unwrap();
+        let expected = game.vertices()[0].pos;
+        assert_eq!(expected, Vector::new(3, -2));
+        let mut replay = Headless::new::<Pad>(WindowBuilder::new("", 800, 600)).unwrap();
+        replay.window_mut().replay(log);
+        replay.event(Event::GamepadAxis(3, GamepadAxis::LeftStickX, 1.0)).unwrap();
+        assert!(replay.window().gamepads().is_empty());
+        replay.step(9).unwrap();
+        assert_eq!(replay.vertices()[0].pos, expected);
+        assert!(!replay.window().is_replaying());
+    }
+}
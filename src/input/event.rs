@@ -9,7 +9,7 @@ use graphics::Window;
 use glutin::{EventsLoop, Event::{WindowEvent}};
 
 /// An input event
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum Event {
     /// The application has been closed
     Closed,
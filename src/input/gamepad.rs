@@ -16,21 +16,27 @@ pub struct Gamepad {
 }
 
 impl Gamepad {
+    // A gamepad with no buttons pressed and its sticks at rest
+    pub(crate) fn new(id: u32) -> Gamepad {
+        Gamepad { id, buttons: [ButtonState::NotPressed; 17], axes: [0.0; 4] }
+    }
+
     pub(crate) fn clear_temporary_states(&mut self) {
         for button in self.buttons.iter_mut() {
             *button =  button.clear_temporary();
         }
     }
 
-    pub(crate) fn set_previous(&mut self, previous: &Gamepad, events: &mut Vec<Event>) {
+    // Add the events that turn a previous state of the gamepad into this one
+    pub(crate) fn changes_from(&self, previous: &Gamepad, events: &mut Vec<Event>) {
         for button in GAMEPAD_BUTTON_LIST.iter() {
             if self[*button].is_down() != previous[*button].is_down() {
-                self.buttons[*button as usize] = if self[*button].is_down() {
+                let state = if self[*button].is_down() {
                     ButtonState::Pressed
                 } else {
                     ButtonState::Released
                 };
-                events.push(Event::GamepadButton(self.id(), *button, self[*button]));
+                events.push(Event::GamepadButton(self.id(), *button, state));
             }
         }
         for axis in GAMEPAD_AXIS_LIST.iter() {
@@ -40,6 +46,14 @@ impl Gamepad {
         }
     }
 
+    pub(crate) fn set_button(&mut self, button: GamepadButton, state: ButtonState) {
+        self.buttons[button as usize] = state;
+    }
+
+    pub(crate) fn set_axis(&mut self, axis: GamepadAxis, value: f32) {
+        self.axes[axis as usize] = value;
+    }
+
     /// Get the ID of the gamepad
     pub fn id(&self) -> u32 {
         self.id
@@ -48,14 +62,23 @@ impl Gamepad {
 
 pub(crate) struct GamepadProvider {
     #[cfg(all(not(any(target_arch="wasm32", target_os="macos")), feature = "gamepads"))]
-    gilrs: gilrs::Gilrs
+    gilrs: Option<gilrs::Gilrs>
 }
 
 impl GamepadProvider {
     pub fn new() -> GamepadProvider {
         GamepadProvider {
             #[cfg(all(not(any(target_arch="wasm32", target_os="macos")), feature = "gamepads"))]
-            gilrs: gilrs::Gilrs::new().unwrap()
+            gilrs: Some(gilrs::Gilrs::new().unwrap())
+        }
+    }
+
+    // A provider that never finds any gamepads, for windows that aren't connected to a display
+    #[cfg(not(target_arch="wasm32"))]
+    pub fn headless() -> GamepadProvider {
+        GamepadProvider {
+            #[cfg(all(not(target_os="macos"), feature = "gamepads"))]
+            gilrs: None
         }
     }
 
@@ -65,8 +88,12 @@ impl GamepadProvider {
 
     #[cfg(all(not(any(target_arch="wasm32", target_os="macos")), feature = "gamepads"))]
     fn provide_gamepads_impl(&mut self, buffer: &mut Vec<Gamepad>) {
-        while let Some(ev) = self.gilrs.next_event() {
-            self.gilrs.update(&ev);
+        let gilrs = match self.gilrs {
+            Some(ref mut gilrs) => gilrs,
+            None => return
+        };
+        while let Some(ev) = gilrs.next_event() {
+            gilrs.update(&ev);
         }
         use gilrs::{
             Axis,
@@ -78,7 +105,7 @@ impl GamepadProvider {
                 None => 0.0
             }
         }
-        buffer.extend(self.gilrs.gamepads().map(|(id, gamepad)| {
+        buffer.extend(gilrs.gamepads().map(|(id, gamepad)| {
             let id = id as u32;
             
             let axes = [
@@ -134,7 +161,7 @@ impl GamepadProvider {
     }
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
 /// The axes a gamepad can report
 pub enum GamepadAxis {
     /// The horizontal tilt of the left stick
@@ -148,7 +175,7 @@ pub enum GamepadAxis {
 }
 
 #[repr(u32)]
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
 /// A button on a gamepad
 pub enum GamepadButton {
     /// The bottom face button
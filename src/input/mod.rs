@@ -9,6 +9,7 @@ mod gamepad;
 mod key;
 mod keyboard;
 mod mouse;
+mod recording;
 
 pub use self::{
     button_state::ButtonState,
@@ -16,11 +17,13 @@ pub use self::{
     key::Key,
     gamepad::{Gamepad, GamepadAxis, GamepadButton},
     keyboard::Keyboard,
-    mouse::{Mouse, MouseButton}
+    mouse::{Mouse, MouseButton},
+    recording::{InputLog, RecordedEvent}
 };
 pub(crate) use self::{
     gamepad::GamepadProvider,
-    key::KEY_LIST
+    key::KEY_LIST,
+    recording::{Recorder, Replayer}
 };
 #[cfg(not(target_arch="wasm32"))] pub(crate) use self::event::EventProvider;
 #[cfg(target_arch="wasm32")] pub(crate) use self::{
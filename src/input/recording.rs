@@ -0,0 +1,156 @@
+#[cfg(feature="saving")]
+extern crate serde_json;
+
+use input::Event;
+#[cfg(feature="saving")]
+use Result;
+
+/// An event tagged with the update it arrived before
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RecordedEvent {
+    /// The number of updates between the start of the recording and the event
+    pub tick: u64,
+    /// The event itself
+    pub event: Event
+}
+
+/// A log of the input events sent to a Window, which can be replayed later
+///
+/// Updates happen at a fixed rate, so replaying the same events on the same ticks reproduces a
+/// run of the game exactly, as long as the game itself is deterministic. The log can be saved
+/// with serde, through `to_json` or the `saving` module.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct InputLog {
+    events: Vec<RecordedEvent>
+}
+
+impl InputLog {
+    /// Create an empty log
+    pub fn new() -> InputLog {
+        InputLog { events: Vec::new() }
+    }
+
+    /// Add an event to the end of the log
+    ///
+    /// Events should be pushed in order, with their ticks never decreasing
+    pub fn push(&mut self, tick: u64, event: Event) {
+        self.events.push(RecordedEvent { tick, event });
+    }
+
+    /// Create a copy of the log with an event added to the end
+    pub fn with(mut self, tick: u64, event: Event) -> InputLog {
+        self.push(tick, event);
+        self
+    }
+
+    /// Get the recorded events, in the order they arrived
+    pub fn events(&self) -> &[RecordedEvent] {
+        &self.events
+    }
+
+    /// The number of events in the log
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Serialize the log into a JSON string
+    #[cfg(feature="saving")]
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Deserialize a log from a JSON string
+    #[cfg(feature="saving")]
+    pub fn from_json(json: &str) -> Result<InputLog> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+// Records the events sent to a Window, counting ticks from when the recording started
+pub(crate) struct Recorder {
+    start: u64,
+    log: InputLog
+}
+
+impl Recorder {
+    pub fn new(start: u64) -> Recorder {
+        Recorder { start, log: InputLog::new() }
+    }
+
+    pub fn record(&mut self, tick: u64, event: Event) {
+        self.log.push(tick - self.start, event);
+    }
+
+    pub fn finish(self) -> InputLog {
+        self.log
+    }
+}
+
+// Plays back the events of a log, counting ticks from when the replay started
+pub(crate) struct Replayer {
+    start: u64,
+    log: InputLog,
+    next: usize
+}
+
+impl Replayer {
+    pub fn new(start: u64, log: InputLog) -> Replayer {
+        Replayer { start, log, next: 0 }
+    }
+
+    // Take the next event if it is due by the given tick
+    pub fn next(&mut self, tick: u64) -> Option<Event> {
+        match self.log.events.get(self.next) {
+            Some(recorded) if recorded.tick + self.start <= tick => {
+                self.next += 1;
+                Some(recorded.event)
+            }
+            _ => None
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.log.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input::{ButtonState, Key};
+
+    #[test]
+    fn relative_ticks() {
+        let mut recorder = Recorder::new(10);
+        recorder.record(10, Event::Focused);
+        recorder.record(13, Event::Unfocused);
+        let log = recorder.finish();
+        assert_eq!(log.events()[0].tick, 0);
+        assert_eq!(log.events()[1].tick, 3);
+    }
+
+    #[test]
+    fn replay_order() {
+        let log = InputLog::new()
+            .with(0, Event::Key(Key::A, ButtonState::Pressed))
+            .with(2, Event::Key(Key::A, ButtonState::Released))
+            .with(2, Event::Closed);
+        let mut replayer = Replayer::new(5, log);
+        assert_eq!(replayer.next(4), None);
+        assert_eq!(replayer.next(5), Some(Event::Key(Key::A, ButtonState::Pressed)));
+        assert_eq!(replayer.next(6), None);
+        assert_eq!(replayer.next(7), Some(Event::Key(Key::A, ButtonState::Released)));
+        assert_eq!(replayer.next(7), Some(Event::Closed));
+        assert!(replayer.is_finished());
+    }
+
+    #[cfg(feature="saving")]
+    #[test]
+    fn json() {
+        use geom::Vector;
+        let log = InputLog::new()
+            .with(1, Event::MouseMoved(Vector::new(3, 4)))
+            .with(4, Event::Key(Key::Space, ButtonState::Released));
+        assert_eq!(InputLog::from_json(&log.to_json().unwrap()).unwrap(), log);
+    }
+}
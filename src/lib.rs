@@ -12,7 +12,7 @@
 //! extern crate quicksilver;
 //! 
 //! use quicksilver::{
-//!     State, run,
+//!     Result, State, run,
 //!     geom::{Circle, Rectangle, Transform},
 //!     graphics::{Color, Draw, Window, WindowBuilder}
 //! };
@@ -20,19 +20,20 @@
 //! struct DrawGeometry;
 //! 
 //! impl State for DrawGeometry {
-//!     fn new() -> DrawGeometry { DrawGeometry }
+//!     fn new() -> Result<DrawGeometry> { Ok(DrawGeometry) }
 //! 
-//!    fn draw(&mut self, window: &mut Window) {
+//!    fn draw(&mut self, window: &mut Window) -> Result<()> {
 //!         window.clear(Color::black());
 //!         window.draw(&Draw::rectangle(Rectangle::new(100, 100, 32, 32)).with_color(Color::red()));
 //!         window.draw(&Draw::rectangle(Rectangle::new(400, 300, 32, 32)).with_color(Color::blue()).with_transform(Transform::rotate(45)).with_z(10));
 //!         window.draw(&Draw::circle(Circle::new(400, 300, 100)).with_color(Color::green()));
 //!         window.present();
+//!         Ok(())
 //!    }
 //! }
 //! 
 //! fn main() {
-//!     run::<DrawGeometry>(WindowBuilder::new("Draw Geometry", 800, 600));
+//!     run::<DrawGeometry>(WindowBuilder::new("Draw Geometry", 800, 600)).unwrap();
 //! }
 //! ```
 //! Run this with `cargo run` or, if you have the wasm32 toolchain installed, you can build for the web 
@@ -47,11 +48,14 @@
 //! The optional features available are 
 //! collision support (via [ncollide2d](https://github.com/sebcrozet/ncollide)), 
 //! font support (via [rusttype](https://github.com/redox-os/rusttype)), 
+//! loading TexturePacker and Aseprite JSON atlases (via [serde_json](https://github.com/serde-rs/json)), 
 //! gamepad support (via [gilrs](https://gitlab.com/gilrs-project/gilrs)), 
 //! saving (via [serde_json](https://github.com/serde-rs/json)),
 //! and sounds (via [rodio](https://github.com/tomaka/rodio)). 
 //! 
-//! Each are enabled by default, but you can [specify which features](https://doc.rust-lang.org/cargo/reference/specifying-dependencies.html#choosing-features) you actually want to use. 
+//! Each are enabled by default, except for capturing the window to animated GIFs and PNGs on
+//! desktop (via [gif](https://github.com/image-rs/image-gif) and [deflate](https://github.com/image-rs/deflate-rs)). 
+//! You can [specify which features](https://doc.rust-lang.org/cargo/reference/specifying-dependencies.html#choosing-features) you actually want to use. 
 
 #![doc(html_root_url = "https://docs.rs/quicksilver/0.2.0")]
 #![deny(missing_docs)]
@@ -69,6 +73,12 @@ extern crate image;
 
 #[cfg(feature="alga")]
 extern crate alga;
+#[cfg(all(feature="color_quant", not(target_arch="wasm32")))] 
+extern crate color_quant;
+#[cfg(all(feature="deflate", not(target_arch="wasm32")))] 
+extern crate deflate;
+#[cfg(all(feature="gif", not(target_arch="wasm32")))] 
+extern crate gif;
 #[cfg(all(feature="gilrs", not(target_arch="wasm32")))] 
 extern crate gilrs;
 #[cfg(feature="nalgebra")]
@@ -86,6 +96,9 @@ extern crate serde_json;
 mod error;
 mod file;
 mod ffi;
+#[cfg(not(target_arch="wasm32"))]
+mod headless;
+mod loading;
 mod state;
 mod timer;
 pub mod geom;
@@ -93,13 +106,18 @@ pub mod graphics;
 pub mod input;
 #[cfg(feature="saving")]
 pub mod saving;
+pub mod scheduler;
 #[cfg(feature="sounds")]
 pub mod sound;
+pub mod tween;
 pub use file::FileLoader;
-pub use error::QuicksilverError;
-pub use timer::Timer;
-pub use state::{State, run};
-#[cfg(target_arch="wasm32")] pub use state::{update, draw, event};
+#[cfg(not(target_arch="wasm32"))]
+pub use headless::Headless;
+pub use loading::{Assets, Load, LoadingScreen};
+pub use error::{QuicksilverError, Result};
+pub use timer::{Clock, SystemClock, Timer};
+pub use state::{State, Transition, run};
+#[cfg(target_arch="wasm32")] pub use state::{frame, event};
 
 /// Necessary types from futures-rs
 pub use futures::{Future, Async};
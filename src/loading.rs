@@ -0,0 +1,310 @@
+use error::QuicksilverError;
+use futures::{Async, Future, Poll};
+use geom::{Rectangle, Vector};
+use graphics::{Color, Draw, Window};
+use state::{State, Transition};
+use std::io::{Error as IOError, ErrorKind};
+use Result;
+
+/// A group of assets that are loaded together
+///
+/// Every time the group is polled, each asset that hasn't finished is polled once, so the group
+/// can report how much of it has loaded. All of the assets in a group have the same type; to load
+/// different kinds of assets together, map each Future into a shared type such as an enum.
+///
+/// The group finishes when it returns the assets or the first error. After that, polling it again
+/// returns an error.
+pub struct Assets<T> {
+    loading: Vec<Option<Box<Future<Item = T, Error = QuicksilverError>>>>,
+    loaded: Vec<Option<T>>,
+    remaining: usize,
+    finished: bool
+}
+
+impl<T> Assets<T> {
+    /// Create an empty group of assets
+    pub fn new() -> Assets<T> {
+        Assets {
+            loading: Vec::new(),
+            loaded: Vec::new(),
+            remaining: 0,
+            finished: false
+        }
+    }
+
+    /// Add an asset to the group, returning its index in the list of loaded assets
+    pub fn add<F>(&mut self, asset: F) -> usize where F: 'static + Future<Item = T, Error = QuicksilverError> {
+        self.loading.push(Some(Box::new(asset)));
+        self.loaded.push(None);
+        self.remaining += 1;
+        self.loading.len() - 1
+    }
+
+    /// Create a copy of the group with an asset added to it
+    pub fn with<F>(mut self, asset: F) -> Assets<T> where F: 'static + Future<Item = T, Error = QuicksilverError> {
+        self.add(asset);
+        self
+    }
+
+    /// The number of assets in the group
+    pub fn len(&self) -> usize {
+        self.loading.len()
+    }
+
+    /// The number of assets in the group that have finished loading
+    pub fn loaded(&self) -> usize {
+        self.len() - self.remaining
+    }
+
+    /// The fraction of the assets that have finished loading, from 0 to 1
+    ///
+    /// An empty group is always fully loaded
+    pub fn progress(&self) -> f32 {
+        if self.len() == 0 {
+            1.0
+        } else {
+            self.loaded() as f32 / self.len() as f32
+        }
+    }
+
+    /// Check if every asset in the group has finished loading
+    pub fn is_loaded(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+impl<T> Future for Assets<T> {
+    type Item = Vec<T>;
+    type Error = QuicksilverError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if self.finished {
+            return Err(IOError::new(ErrorKind::Other, "The assets already finished loading or failed to load").into());
+        }
+        for i in 0..self.loading.len() {
+            let result = match self.loading[i] {
+                Some(ref mut future) => future.poll(),
+                None => continue
+            };
+            match result {
+                Ok(Async::Ready(asset)) => {
+                    self.loading[i] = None;
+                    self.loaded[i] = Some(asset);
+                    self.remaining -= 1;
+                }
+                Ok(Async::NotReady) => (),
+                Err(error) => {
+                    // The group won't finish without the asset, so stop loading the rest too
+                    self.loading.iter_mut().for_each(|future| *future = None);
+                    self.finished = true;
+                    return Err(error);
+                }
+            }
+        }
+        Ok(if self.is_loaded() {
+            self.finished = true;
+            Async::Ready(self.loaded.drain(..).map(Option::unwrap).collect())
+        } else {
+            Async::NotReady
+        })
+    }
+}
+
+/// A State that is created from a group of assets once they have loaded
+///
+/// To load the assets, run a `LoadingScreen` of the State rather than the State itself. The
+/// LoadingScreen creates the State through `from_assets` instead of `State::new`.
+pub trait Load: State + Sized + 'static {
+    /// The type of the assets the State is created from
+    type Asset: 'static;
+
+    /// Start loading the assets the State needs
+    fn load() -> Assets<Self::Asset>;
+
+    /// Create the State from the loaded assets, which are in the order they were added
+    fn from_assets(assets: Vec<Self::Asset>, window: &mut Window) -> Result<Self>;
+
+    /// Draw the loading screen given the fraction of the assets that have loaded
+    ///
+    /// By default it draws a progress bar in the middle of a black screen
+    fn draw_progress(progress: f32, window: &mut Window) -> Result<()> {
+        let screen = window.screen_size();
+        let bar = Rectangle::newv(Vector::new(screen.x / 4.0, screen.y / 2.0 - 8.0), Vector::new(screen.x / 2.0, 16.0));
+        let filled = Rectangle::newv(bar.top_left(), Vector::new(bar.width * progress, bar.height));
+        window.clear(Color::black());
+        window.draw(&Draw::rectangle(bar).with_color(Color::white().with_alpha(0.25)));
+        window.draw(&Draw::rectangle(filled).with_color(Color::white()).with_z(1));
+        window.present();
+        Ok(())
+    }
+
+    /// Handle an error from loading the assets, creating the State, or entering it
+    ///
+    /// By default it returns the error, stopping the game loop. If it returns `Ok`, the loading
+    /// screen starts loading the assets again with `load`.
+    fn handle_error(error: QuicksilverError, _window: &mut Window) -> Result<()> {
+        Err(error)
+    }
+}
+
+/// A State that loads the assets of another State, and then replaces itself with that State
+///
+/// Every update polls the assets once, and every draw shows the progress through
+/// `Load::draw_progress`.
+pub struct LoadingScreen<T: Load> {
+    assets: Assets<T::Asset>
+}
+
+impl<T: Load> LoadingScreen<T> {
+    /// The fraction of the assets that have finished loading, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        self.assets.progress()
+    }
+}
+
+impl<T: Load> State for LoadingScreen<T> {
+    fn new() -> Result<LoadingScreen<T>> {
+        Ok(LoadingScreen {
+            assets: T::load()
+        })
+    }
+
+    fn update(&mut self, window: &mut Window) -> Result<Transition> {
+        Ok(match self.assets.poll()? {
+            Async::Ready(assets) => Transition::Replace(Box::new(T::from_assets(assets, window)?)),
+            Async::NotReady => Transition::None
+        })
+    }
+
+    fn draw(&mut self, window: &mut Window) -> Result<()> {
+        T::draw_progress(self.assets.progress(), window)
+    }
+
+    fn handle_error(&mut self, error: QuicksilverError, window: &mut Window) -> Result<()> {
+        <T as Load>::handle_error(error, window)?;
+        self.assets = T::load();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::{empty, err, ok, poll_fn};
+    use graphics::WindowBuilder;
+    use std::cell::Cell;
+    use std::io::{Error as IOError, ErrorKind};
+    use Headless;
+
+    #[test]
+    fn empty_group() {
+        let mut assets = Assets::<u32>::new();
+        assert_eq!(assets.progress(), 1.0);
+        assert_eq!(assets.poll().unwrap(), Async::Ready(Vec::new()));
+    }
+
+    #[test]
+    fn progress() {
+        let mut polls = 0;
+        let mut assets = Assets::new()
+            .with(ok(1))
+            .with(empty())
+            .with(poll_fn(move || {
+                polls += 1;
+                Ok(if polls < 2 { Async::NotReady } else { Async::Ready(3) })
+            }));
+        assert_eq!(assets.len(), 3);
+        assert_eq!(assets.progress(), 0.0);
+        assert_eq!(assets.poll().unwrap(), Async::NotReady);
+        assert_eq!(assets.loaded(), 1);
+        assert_eq!(assets.poll().unwrap(), Async::NotReady);
+        assert_eq!(assets.loaded(), 2);
+        assert!(!assets.is_loaded());
+    }
+
+    #[test]
+    fn ordering() {
+        let mut polls = 0;
+        let mut assets = Assets::new();
+        assets.add(poll_fn(move || {
+            polls += 1;
+            Ok(if polls < 3 { Async::NotReady } else { Async::Ready("slow") })
+        }));
+        assets.add(ok("fast"));
+        assert_eq!(assets.poll().unwrap(), Async::NotReady);
+        assert_eq!(assets.poll().unwrap(), Async::NotReady);
+        assert_eq!(assets.poll().unwrap(), Async::Ready(vec!["slow", "fast"]));
+    }
+
+    #[test]
+    fn error() {
+        let mut assets = Assets::<u32>::new()
+            .with(err(IOError::new(ErrorKind::NotFound, "missing").into()));
+        assert!(assets.poll().is_err());
+    }
+
+    #[test]
+    fn finished() {
+        let mut polls = 0;
+        let mut assets = Assets::<u32>::new()
+            .with(poll_fn(move || {
+                polls += 1;
+                assert_eq!(polls, 1, "A failed group should stop polling its assets");
+                Ok(Async::NotReady)
+            }))
+            .with(err(IOError::new(ErrorKind::NotFound, "missing").into()));
+        assert!(assets.poll().is_err());
+        assert!(assets.poll().is_err());
+        let mut loaded = Assets::new().with(ok(1));
+        assert_eq!(loaded.poll().unwrap(), Async::Ready(vec![1]));
+        assert!(loaded.poll().is_err());
+    }
+
+    thread_local! {
+        static LOADS: Cell<u32> = Cell::new(0);
+        static ERRORS: Cell<u32> = Cell::new(0);
+        static CREATED: Cell<Option<u32>> = Cell::new(None);
+    }
+
+    // Fails to load the first time, and loads on the retry
+    struct Retry;
+
+    impl State for Retry {
+        fn new() -> Result<Retry> {
+            Ok(Retry)
+        }
+    }
+
+    impl Load for Retry {
+        type Asset = u32;
+
+        fn load() -> Assets<u32> {
+            LOADS.with(|loads| loads.set(loads.get() + 1));
+            if LOADS.with(Cell::get) == 1 {
+                Assets::new().with(err(IOError::new(ErrorKind::NotFound, "missing").into()))
+            } else {
+                Assets::new().with(ok(5))
+            }
+        }
+
+        fn from_assets(assets: Vec<u32>, _: &mut Window) -> Result<Retry> {
+            CREATED.with(|created| created.set(Some(assets[0])));
+            Ok(Retry)
+        }
+
+        fn handle_error(_: QuicksilverError, _: &mut Window) -> Result<()> {
+            ERRORS.with(|errors| errors.set(errors.get() + 1));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn retry() {
+        let mut game = Headless::new::<LoadingScreen<Retry>>(WindowBuilder::new("", 64, 64)).unwrap();
+        game.step(1).unwrap();
+        assert_eq!((LOADS.with(Cell::get), ERRORS.with(Cell::get)), (2, 1));
+        assert_eq!(CREATED.with(Cell::get), None);
+        game.step(1).unwrap();
+        assert_eq!(CREATED.with(Cell::get), Some(5));
+    }
+}
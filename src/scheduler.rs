@@ -0,0 +1,323 @@
+//! A module for running code at points in the future of the game loop
+//!
+//! Every Window has a Scheduler, which is advanced once per update, just before `State::update`
+//! runs. Tasks can run after a delay, repeatedly, or once a condition is met, and a Script
+//! strings several of those together into a sequence such as a cutscene.
+//!
+//! Delays are counted in ticks (updates), or in milliseconds that are converted to ticks using
+//! the Window's update rate.
+
+use graphics::Window;
+use std::cell::Cell;
+use std::mem;
+use std::rc::Rc;
+
+/// A handle to a scheduled task, which can cancel it
+///
+/// Dropping the handle does not cancel the task.
+#[derive(Clone, Debug)]
+pub struct TaskHandle {
+    active: Rc<Cell<bool>>
+}
+
+impl TaskHandle {
+    /// Stop the task from running again
+    pub fn cancel(&self) {
+        self.active.set(false);
+    }
+
+    /// Check if the task is still scheduled, or if it has finished or been cancelled
+    pub fn is_active(&self) -> bool {
+        self.active.get()
+    }
+}
+
+enum Step {
+    Run(Box<FnMut(&mut Window)>),
+    Wait(u32),
+    WaitMillis(f64),
+    WaitUntil(Box<FnMut(&mut Window) -> bool>)
+}
+
+/// A scripted sequence of actions and waits, run a step at a time by the Scheduler
+///
+/// Each tick the script runs as many steps as it can, stopping at the first wait that hasn't
+/// finished. A looping script starts over from its first step on the tick after it reaches the
+/// end.
+pub struct Script {
+    steps: Vec<Step>,
+    looping: bool,
+    index: usize,
+    waited: u32
+}
+
+impl Script {
+    /// Create an empty script
+    pub fn new() -> Script {
+        Script {
+            steps: Vec::new(),
+            looping: false,
+            index: 0,
+            waited: 0
+        }
+    }
+
+    /// Add an action to the end of the script
+    pub fn then<F>(mut self, action: F) -> Script where F: 'static + FnMut(&mut Window) {
+        self.steps.push(Step::Run(Box::new(action)));
+        self
+    }
+
+    /// Add a wait of a number of ticks to the end of the script
+    pub fn wait(mut self, ticks: u32) -> Script {
+        self.steps.push(Step::Wait(ticks));
+        self
+    }
+
+    /// Add a wait of a number of milliseconds to the end of the script
+    pub fn wait_millis(mut self, millis: f64) -> Script {
+        self.steps.push(Step::WaitMillis(millis));
+        self
+    }
+
+    /// Add a wait that lasts until a condition is true to the end of the script
+    ///
+    /// The condition is checked once per tick
+    pub fn wait_until<F>(mut self, condition: F) -> Script where F: 'static + FnMut(&mut Window) -> bool {
+        self.steps.push(Step::WaitUntil(Box::new(condition)));
+        self
+    }
+
+    /// Set if the script should start over when it reaches the end (defaults to false)
+    pub fn with_looping(self, looping: bool) -> Script {
+        Script { looping, ..self }
+    }
+
+    // Run the script for a tick, returning true when it has finished
+    fn advance(&mut self, window: &mut Window) -> bool {
+        // A wait only counts the current tick if it was already waiting when the tick began
+        let mut resumed = true;
+        loop {
+            if self.index >= self.steps.len() {
+                if !self.looping || self.steps.is_empty() {
+                    return true;
+                }
+                // Only loop once per tick, or a script without waits would never yield
+                self.index = 0;
+                return false;
+            }
+            let done = match self.steps[self.index] {
+                Step::Run(ref mut action) => {
+                    action(window);
+                    true
+                }
+                Step::Wait(ticks) => self.wait_for(ticks, resumed),
+                Step::WaitMillis(millis) => {
+                    let ticks = (millis / window.update_rate()).round() as u32;
+                    self.wait_for(ticks, resumed)
+                }
+                Step::WaitUntil(ref mut condition) => condition(window)
+            };
+            if !done {
+                return false;
+            }
+            self.index += 1;
+            resumed = false;
+        }
+    }
+
+    fn wait_for(&mut self, ticks: u32, counts: bool) -> bool {
+        if counts {
+            self.waited += 1;
+        }
+        if self.waited >= ticks {
+            self.waited = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+struct Task {
+    script: Script,
+    active: Rc<Cell<bool>>
+}
+
+/// A list of tasks that are run as the game loop ticks
+///
+/// Get the Window's Scheduler with `Window::scheduler`.
+pub struct Scheduler {
+    tasks: Vec<Task>,
+    cleared: bool
+}
+
+impl Scheduler {
+    pub(crate) fn new() -> Scheduler {
+        Scheduler { tasks: Vec::new(), cleared: false }
+    }
+
+    /// Start running a script, beginning on the next tick
+    pub fn run(&mut self, script: Script) -> TaskHandle {
+        let active = Rc::new(Cell::new(true));
+        self.tasks.push(Task { script, active: active.clone() });
+        TaskHandle { active }
+    }
+
+    /// Run an action once, a number of ticks from now
+    pub fn after<F>(&mut self, ticks: u32, action: F) -> TaskHandle where F: 'static + FnMut(&mut Window) {
+        self.run(Script::new().wait(ticks).then(action))
+    }
+
+    /// Run an action once, a number of milliseconds from now
+    pub fn after_millis<F>(&mut self, millis: f64, action: F) -> TaskHandle where F: 'static + FnMut(&mut Window) {
+        self.run(Script::new().wait_millis(millis).then(action))
+    }
+
+    /// Run an action every time a number of ticks passes, until it is cancelled
+    pub fn every<F>(&mut self, ticks: u32, action: F) -> TaskHandle where F: 'static + FnMut(&mut Window) {
+        self.run(Script::new().wait(ticks).then(action).with_looping(true))
+    }
+
+    /// Run an action every time a number of milliseconds passes, until it is cancelled
+    pub fn every_millis<F>(&mut self, millis: f64, action: F) -> TaskHandle where F: 'static + FnMut(&mut Window) {
+        self.run(Script::new().wait_millis(millis).then(action).with_looping(true))
+    }
+
+    /// Run an action once, on the first tick that a condition is true
+    pub fn when<C, F>(&mut self, condition: C, action: F) -> TaskHandle
+            where C: 'static + FnMut(&mut Window) -> bool, F: 'static + FnMut(&mut Window) {
+        self.run(Script::new().wait_until(condition).then(action))
+    }
+
+    /// The number of tasks that are still scheduled
+    pub fn len(&self) -> usize {
+        self.tasks.iter().filter(|task| task.active.get()).count()
+    }
+
+    /// Cancel every scheduled task
+    pub fn cancel_all(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.active.set(false);
+        }
+        self.cleared = true;
+    }
+
+    // Run every task for a tick
+    //
+    // The tasks are taken out of the Window while they run, so that they can schedule more tasks;
+    // those start on the next tick.
+    pub(crate) fn advance(window: &mut Window) {
+        let tasks = mem::replace(&mut window.scheduler.tasks, Vec::new());
+        let mut remaining = Vec::with_capacity(tasks.len());
+        window.scheduler.cleared = false;
+        for mut task in tasks {
+            // A task may have cancelled every task, including the ones taken out of the Window
+            if window.scheduler.cleared {
+                task.active.set(false);
+            }
+            if task.active.get() {
+                if task.script.advance(window) {
+                    task.active.set(false);
+                } else {
+                    remaining.push(task);
+                }
+            }
+        }
+        if window.scheduler.cleared {
+            for task in remaining.drain(..) {
+                task.active.set(false);
+            }
+        }
+        remaining.append(&mut window.scheduler.tasks);
+        window.scheduler.tasks = remaining;
+    }
+}
+
+#[cfg(all(test, not(target_arch="wasm32")))]
+mod tests {
+    use super::*;
+    use graphics::WindowBuilder;
+
+    fn window() -> Window {
+        WindowBuilder::new("", 800, 600)
+            .with_update_rate(10.0)
+            .build_headless()
+    }
+
+    fn counter() -> (Rc<Cell<u32>>, impl FnMut(&mut Window)) {
+        let count = Rc::new(Cell::new(0));
+        let counter = count.clone();
+        (count, move |_: &mut Window| counter.set(counter.get() + 1))
+    }
+
+    fn advance(window: &mut Window, ticks: u32) {
+        for _ in 0..ticks {
+            Scheduler::advance(window);
+        }
+    }
+
+    #[test]
+    fn after() {
+        let mut window = window();
+        let (count, action) = counter();
+        let handle = window.scheduler().after(30, action);
+        advance(&mut window, 29);
+        assert_eq!(count.get(), 0);
+        advance(&mut window, 1);
+        assert_eq!(count.get(), 1);
+        assert!(!handle.is_active());
+        advance(&mut window, 100);
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn every() {
+        let mut window = window();
+        let (count, action) = counter();
+        let handle = window.scheduler().every_millis(50.0, action);
+        advance(&mut window, 26);
+        assert_eq!(count.get(), 5);
+        handle.cancel();
+        advance(&mut window, 10);
+        assert_eq!(count.get(), 5);
+        assert_eq!(window.scheduler().len(), 0);
+    }
+
+    #[test]
+    fn when() {
+        let mut window = window();
+        let (count, action) = counter();
+        let ready = Rc::new(Cell::new(false));
+        let flag = ready.clone();
+        window.scheduler().when(move |_| flag.get(), action);
+        advance(&mut window, 5);
+        assert_eq!(count.get(), 0);
+        ready.set(true);
+        advance(&mut window, 5);
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn script() {
+        let mut window = window();
+        let log = Rc::new(Cell::new(0));
+        let (first, second) = (log.clone(), log.clone());
+        window.scheduler().run(Script::new()
+            .then(move |_| first.set(1))
+            .wait(2)
+            .then(move |window| {
+                second.set(2);
+                let third = second.clone();
+                window.scheduler().after(1, move |_| third.set(3));
+            }));
+        advance(&mut window, 1);
+        assert_eq!(log.get(), 1);
+        advance(&mut window, 1);
+        assert_eq!(log.get(), 1);
+        advance(&mut window, 1);
+        assert_eq!(log.get(), 2);
+        advance(&mut window, 1);
+        assert_eq!(log.get(), 3);
+    }
+}
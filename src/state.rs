@@ -1,132 +1,322 @@
 #[cfg(target_arch="wasm32")]
 use geom::Vector;
+use error::QuicksilverError;
 use graphics::{Window, WindowBuilder};
 use input::Event;
+use scheduler::Scheduler;
+use timer::{Timer, current_time};
+use Result;
 
 /// The structure responsible for managing the game loop state
+///
+/// Each of the lifecycle functions can fail. An error is first passed to `State::handle_error`,
+/// and if that doesn't recover from it the game loop stops and `run` returns the error.
 pub trait State {
     /// Create the state given the window and canvas
-    fn new() -> Self where Self: Sized;
+    fn new() -> Result<Self> where Self: Sized;
     /// Tick the State forward one frame
     ///
-    /// Will happen at a fixed rate of 60 ticks per second under ideal conditions. Under non-ideal conditions,
-    /// the game loop will do its best to still call the update at about 60 TPS. 
+    /// Will happen at a fixed rate, by default 60 ticks per second, which can be changed with
+    /// `WindowBuilder::with_update_rate`. If the game loop falls behind it will run several updates
+    /// in a row to catch up, up to the limit set by `WindowBuilder::with_max_updates`.
+    ///
+    /// The returned Transition can change which State is running; see `Transition` for details.
     ///
     /// By default it does nothing
-    fn update(&mut self, &mut Window) {}
+    fn update(&mut self, &mut Window) -> Result<Transition> { Ok(Transition::None) }
     /// Process an incoming event
     ///
     /// By default it does nothing
-    fn event(&mut self, &Event, &mut Window) {}
+    fn event(&mut self, &Event, &mut Window) -> Result<()> { Ok(()) }
     /// Draw the state to the screen
     ///
-    /// Will happen as often as possible, only limited by vysnc
+    /// Will happen as often as possible, only limited by vysnc. `Window::alpha` gives how far the
+    /// draw is between the last update and the next one, which can be used to interpolate motion.
     ///
     /// By default it draws a black screen
-    fn draw(&mut self, window: &mut Window) {
+    fn draw(&mut self, window: &mut Window) -> Result<()> {
         use graphics::Color;
         window.clear(Color::black());
         window.present();
+        Ok(())
     }
+    /// Called when the State starts running, either at startup or when it is pushed or swapped in
+    ///
+    /// By default it does nothing
+    fn enter(&mut self, &mut Window) -> Result<()> { Ok(()) }
+    /// Called when the State stops running, either when it is popped or replaced or when the
+    /// application quits
+    ///
+    /// By default it does nothing
+    fn exit(&mut self, &mut Window) -> Result<()> { Ok(()) }
+    /// Called when another State is pushed on top of this one
+    ///
+    /// By default it does nothing
+    fn pause(&mut self, &mut Window) -> Result<()> { Ok(()) }
+    /// Called when the State on top of this one is popped, making this the running State again
+    ///
+    /// By default it does nothing
+    fn resume(&mut self, &mut Window) -> Result<()> { Ok(()) }
+    /// Called when one of the other functions returns an error while this is the running State
+    ///
+    /// Returning `Ok` recovers from the error and keeps the game loop going, and returning `Err`
+    /// stops the game loop. On desktop the error is then returned from `run`; on the web it is
+    /// logged to the browser console.
+    ///
+    /// By default it returns the error, stopping the game loop
+    fn handle_error(&mut self, error: QuicksilverError, &mut Window) -> Result<()> { Err(error) }
+}
+
+/// A change to the stack of running States, returned from `State::update`
+///
+/// Only the State on top of the stack is updated, drawn, and sent events. States further down
+/// are paused until the States above them are popped.
+pub enum Transition {
+    /// Keep running the current State
+    None,
+    /// Pause the current State and start running a new one on top of it
+    ///
+    /// If the new State fails to enter, the current one is resumed and keeps running
+    Push(Box<State>),
+    /// Exit the current State and resume the one below it
+    ///
+    /// If there is no State below it, the application quits
+    Pop,
+    /// Exit the current State and start running a new one in its place
+    ///
+    /// If the new State fails to enter, the current one is entered again and keeps running
+    Replace(Box<State>),
+    /// Exit every State and quit the application
+    Quit
 }
 
 /// Run the application's game loop
 ///
-/// On desktop platforms, this yields control to a simple game loop controlled by a Timer. On wasm,
-/// this yields control to the browser functions setInterval and requestAnimationFrame
-pub fn run<T: 'static + State>(window: WindowBuilder) {
+/// On desktop platforms, this yields control to a simple fixed-timestep game loop, which returns
+/// once the application quits or an error stops it. On wasm, this yields control to the browser
+/// function requestAnimationFrame, which drives the same loop, and returns immediately.
+pub fn run<T: 'static + State>(window: WindowBuilder) -> Result<()> {
     run_impl::<T>(window)
 }
 
 #[doc(hidden)]
 pub struct Application {
-    state: Box<State>, 
-    window: Window,
-    event_buffer: Vec<Event>
+    states: Vec<Box<State>>, 
+    pub(crate) window: Window,
+    event_buffer: Vec<Event>,
+    timer: Timer
 }
 
 impl Application {
-    fn update(&mut self) {
-        self.state.update(&mut self.window);
+    pub(crate) fn new(state: Box<State>, window: Window) -> Result<Application> {
+        let timer = Timer::new(window.update_rate).with_max_ticks(window.max_updates);
+        let mut app = Application {
+            states: Vec::new(),
+            window,
+            event_buffer: Vec::new(),
+            timer
+        };
+        let result = app.transition(Transition::Push(state));
+        app.handle_error(result)?;
+        Ok(app)
     }
 
-    fn draw(&mut self) {
-        self.state.draw(&mut self.window);
+    pub(crate) fn is_running(&self) -> bool {
+        !self.states.is_empty()
+    }
+
+    // Give the running State a chance to recover from an error
+    fn handle_error(&mut self, result: Result<()>) -> Result<()> {
+        match result {
+            Ok(()) => Ok(()),
+            Err(error) => match self.states.last_mut() {
+                Some(state) => state.handle_error(error, &mut self.window),
+                None => Err(error)
+            }
+        }
     }
 
-    #[cfg(target_arch="wasm32")]
-    fn event(&mut self, event: &Event) {
+    // Change the stack of States, which is only changed once the callbacks involved succeed
+    //
+    // If a new State fails to enter, the State it would have covered or replaced is resumed or
+    // entered again, so the stack is left as it was.
+    pub(crate) fn transition(&mut self, transition: Transition) -> Result<()> {
+        match transition {
+            Transition::None => (),
+            Transition::Push(mut state) => {
+                if let Some(current) = self.states.last_mut() {
+                    current.pause(&mut self.window)?;
+                }
+                if let Err(error) = state.enter(&mut self.window) {
+                    if let Some(current) = self.states.last_mut() {
+                        current.resume(&mut self.window)?;
+                    }
+                    return Err(error);
+                }
+                self.states.push(state);
+            }
+            Transition::Pop => {
+                if let Some(current) = self.states.last_mut() {
+                    current.exit(&mut self.window)?;
+                }
+                self.states.pop();
+                if let Some(next) = self.states.last_mut() {
+                    next.resume(&mut self.window)?;
+                }
+            }
+            Transition::Replace(mut state) => {
+                if let Some(current) = self.states.last_mut() {
+                    current.exit(&mut self.window)?;
+                }
+                if let Err(error) = state.enter(&mut self.window) {
+                    if let Some(current) = self.states.last_mut() {
+                        current.enter(&mut self.window)?;
+                    }
+                    return Err(error);
+                }
+                self.states.pop();
+                self.states.push(state);
+            }
+            Transition::Quit => while let Some(current) = self.states.last_mut() {
+                current.exit(&mut self.window)?;
+                self.states.pop();
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn update(&mut self) -> Result<()> {
+        while let Some(event) = self.window.next_replayed_event() {
+            self.event(&event)?;
+        }
+        self.window.tick += 1;
+        Scheduler::advance(&mut self.window);
+        let transition = match self.states.last_mut() {
+            Some(state) => state.update(&mut self.window),
+            None => return Ok(())
+        };
+        let result = match transition {
+            Ok(transition) => self.transition(transition),
+            Err(error) => Err(error)
+        };
+        self.handle_error(result)
+    }
+
+    pub(crate) fn draw(&mut self) -> Result<()> {
+        let result = match self.states.last_mut() {
+            Some(state) => state.draw(&mut self.window),
+            None => Ok(())
+        };
+        self.handle_error(result)
+    }
+
+    // Deliver an event from the user, unless a replay is standing in for them
+    pub(crate) fn input(&mut self, event: &Event) -> Result<()> {
+        if self.window.accepts_input(event) {
+            self.event(event)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn event(&mut self, event: &Event) -> Result<()> {
         self.window.process_event(event);
-        self.state.event(event, &mut self.window);
+        let result = match self.states.last_mut() {
+            Some(state) => state.event(event, &mut self.window),
+            None => Ok(())
+        };
+        self.handle_error(result)
     }
 
-    fn process_events(&mut self) {
+    fn process_events(&mut self) -> Result<()> {
         self.window.update_gamepads(&mut self.event_buffer);
+        let mut result = Ok(());
         for i in 0..self.event_buffer.len() {
-            self.window.process_event(&self.event_buffer[i]);
-            self.state.event(&self.event_buffer[i], &mut self.window);
+            let event = self.event_buffer[i];
+            result = self.input(&event);
+            if result.is_err() {
+                break;
+            }
         }
         self.event_buffer.clear();
+        result
+    }
+
+    // Run as many fixed-rate updates as the elapsed time calls for and then draw once
+    fn frame(&mut self) -> Result<()> {
+        self.timer.set_tick_rate(self.window.update_rate);
+        self.timer.set_max_ticks(self.window.max_updates);
+        for _ in 0..self.timer.advance() {
+            if !self.is_running() {
+                break;
+            }
+            let start = current_time();
+            self.update()?;
+            self.window.stats.record_update(current_time() - start);
+            self.window.clear_temporary_states();
+        }
+        self.window.alpha = self.timer.alpha();
+        let start = current_time();
+        self.draw()?;
+        self.window.stats.record_draw(current_time() - start);
+        self.window.finish_frame();
+        Ok(())
     }
 }
 
 #[cfg(not(target_arch="wasm32"))]
-fn run_impl<T: 'static + State>(window: WindowBuilder) {
+fn run_impl<T: 'static + State>(window: WindowBuilder) -> Result<()> {
     use input::EventProvider;
     let (window, events_loop) = window.build();
     let mut events = EventProvider::new(events_loop);
-    let event_buffer = Vec::new();
-    let state = Box::new(T::new());
-    let mut app = Application { window, state, event_buffer };
-    use std::time::Duration;
+    let state = Box::new(T::new()?);
+    let mut app = Application::new(state, window)?;
     #[cfg(feature="sounds")] {
         use sound::Sound;
         Sound::initialize();
     }
-    let mut timer = ::Timer::new();
     let mut running = true;
-    while running {
+    while running && app.is_running() {
         running = events.generate_events(&mut app.window, &mut app.event_buffer);
-        app.process_events();
-        timer.tick(||  { 
-            app.update(); 
-            Duration::from_millis(16) 
-        });
-        app.draw();
-        app.window.clear_temporary_states();
+        app.process_events()?;
+        app.frame()?;
     }
+    app.transition(Transition::Quit)
 }
 
 #[cfg(target_arch="wasm32")]
-fn run_impl<T: 'static + State>(window: WindowBuilder) {
+fn run_impl<T: 'static + State>(window: WindowBuilder) -> Result<()> {
     use ffi::wasm;
     use std::os::raw::c_void;
     let window = window.build();
-    let app = Box::new(Application { 
-        window,
-        state: Box::new(T::new()),
-        event_buffer: Vec::new()
-    });
+    let app = Box::new(Application::new(Box::new(T::new()?), window)?);
     unsafe { wasm::set_app(Box::into_raw(app) as *mut c_void) };
+    Ok(())
 }
 
-#[doc(hidden)]
-#[no_mangle]
+// Log an error that stopped the game loop and stop running any States
 #[cfg(target_arch="wasm32")]
-pub extern "C" fn update(app: *mut Application) {
-    let mut app = unsafe { Box::from_raw(app) };
-    app.process_events();
-    app.update();
-    app.window.clear_temporary_states();
-    Box::into_raw(app);
+fn stop_on_error(app: &mut Application, result: Result<()>) {
+    if let Err(error) = result {
+        use ffi::wasm;
+        use std::ffi::CString;
+        // A NUL byte would end the message early, so they are taken out, which means the CString
+        // can always be made; the bridge frees it once it has been read
+        let message = CString::new(error.to_string().replace('\0', "")).expect("NUL bytes were removed");
+        unsafe { wasm::log_error(message.into_raw()) };
+        app.states.clear();
+    }
 }
 
 #[doc(hidden)]
 #[no_mangle]
 #[cfg(target_arch="wasm32")]
-pub extern "C" fn draw(app: *mut Application) {
+pub extern "C" fn frame(app: *mut Application) {
     let mut app = unsafe { Box::from_raw(app) };
-    app.draw();
+    if app.is_running() {
+        let result = app.process_events().and_then(|_| app.frame());
+        stop_on_error(&mut app, result);
+    }
     Box::into_raw(app);
 }
 
@@ -156,6 +346,137 @@ pub unsafe extern "C" fn event(app: *mut Application, event_tag: u32) {
             return;
         }
     };
-    app.event(&event);
+    if app.is_running() {
+        let result = app.input(&event);
+        stop_on_error(&mut app, result);
+    }
     Box::into_raw(app);
 }
+
+#[cfg(all(test, not(target_arch="wasm32")))]
+mod tests {
+    use super::*;
+    use headless::Headless;
+    use std::cell::RefCell;
+    use std::io::{Error as IOError, ErrorKind};
+
+    thread_local! {
+        // The callbacks the States have run, in order
+        static LOG: RefCell<Vec<String>> = RefCell::new(Vec::new());
+        // The transition the running State returns from its next update
+        static NEXT: RefCell<Option<Transition>> = RefCell::new(None);
+    }
+
+    fn log(entry: String) {
+        LOG.with(|log| log.borrow_mut().push(entry));
+    }
+
+    fn take_log() -> Vec<String> {
+        LOG.with(|log| log.borrow_mut().drain(..).collect())
+    }
+
+    struct Logged {
+        name: &'static str,
+        fail_enter: bool,
+        fail_exit: bool
+    }
+
+    impl Logged {
+        fn named(name: &'static str) -> Box<State> {
+            Box::new(Logged { name, fail_enter: false, fail_exit: false })
+        }
+
+        fn callback(&self, callback: &str, fail: bool) -> Result<()> {
+            log(format!("{} {}", callback, self.name));
+            if fail {
+                Err(IOError::new(ErrorKind::Other, callback).into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl State for Logged {
+        fn new() -> Result<Logged> {
+            Ok(Logged { name: "root", fail_enter: false, fail_exit: false })
+        }
+
+        fn update(&mut self, _: &mut Window) -> Result<Transition> {
+            Ok(NEXT.with(|next| next.borrow_mut().take()).unwrap_or(Transition::None))
+        }
+
+        fn draw(&mut self, _: &mut Window) -> Result<()> {
+            Ok(())
+        }
+
+        fn enter(&mut self, _: &mut Window) -> Result<()> {
+            self.callback("enter", self.fail_enter)
+        }
+
+        fn exit(&mut self, _: &mut Window) -> Result<()> {
+            self.callback("exit", self.fail_exit)
+        }
+
+        fn pause(&mut self, _: &mut Window) -> Result<()> {
+            self.callback("pause", false)
+        }
+
+        fn resume(&mut self, _: &mut Window) -> Result<()> {
+            self.callback("resume", false)
+        }
+    }
+
+    // Run an update in which the running State returns a transition
+    fn step(game: &mut Headless, transition: Transition) -> Result<()> {
+        NEXT.with(|next| *next.borrow_mut() = Some(transition));
+        game.step(1)
+    }
+
+    fn game() -> Headless {
+        Headless::new::<Logged>(WindowBuilder::new("", 800, 600)).unwrap()
+    }
+
+    #[test]
+    fn callback_order() {
+        let mut game = game();
+        step(&mut game, Transition::Push(Logged::named("menu"))).unwrap();
+        step(&mut game, Transition::Replace(Logged::named("options"))).unwrap();
+        step(&mut game, Transition::Pop).unwrap();
+        assert!(game.is_running());
+        step(&mut game, Transition::Push(Logged::named("pause"))).unwrap();
+        step(&mut game, Transition::Quit).unwrap();
+        assert!(!game.is_running());
+        assert_eq!(take_log(), [
+            "enter root", "pause root", "enter menu", "exit menu", "enter options", "exit options",
+            "resume root", "pause root", "enter pause", "exit pause", "exit root"
+        ]);
+    }
+
+    #[test]
+    fn failed_enter() {
+        let mut game = game();
+        let broken = || Box::new(Logged { name: "broken", fail_enter: true, fail_exit: false });
+        assert!(step(&mut game, Transition::Push(broken())).is_err());
+        assert!(step(&mut game, Transition::Replace(broken())).is_err());
+        // The root State is still running, so the next transition starts from it
+        step(&mut game, Transition::Pop).unwrap();
+        assert!(!game.is_running());
+        assert_eq!(take_log(), [
+            "enter root", "pause root", "enter broken", "resume root",
+            "exit root", "enter broken", "enter root", "exit root"
+        ]);
+    }
+
+    #[test]
+    fn failed_exit() {
+        let mut game = game();
+        step(&mut game, Transition::Push(Box::new(Logged { name: "stuck", fail_enter: false, fail_exit: true }))).unwrap();
+        assert!(step(&mut game, Transition::Replace(Logged::named("menu"))).is_err());
+        assert!(step(&mut game, Transition::Pop).is_err());
+        // The State that failed to exit is still on top
+        step(&mut game, Transition::Push(Logged::named("menu"))).unwrap();
+        assert_eq!(take_log(), [
+            "enter root", "pause root", "enter stuck", "exit stuck", "exit stuck", "pause stuck", "enter menu"
+        ]);
+    }
+}
@@ -1,32 +1,317 @@
-use std::time::{Duration, Instant};
+#[cfg(not(target_arch="wasm32"))]
+use std::time::Instant;
 
-#[derive(Clone, Copy, Debug)] 
-///A structure that allows accumulation of actions in a loop
-pub struct Timer {
-    previous_tick: Instant,
-    wait_time: Duration
+/// A source of time for a Timer
+///
+/// The default is the `SystemClock`, but tests can provide a clock they control.
+pub trait Clock {
+    /// The number of milliseconds since some fixed point in the past
+    fn now(&self) -> f64;
+}
+
+/// The clock the game loop runs on
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        current_time()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+///A fixed-rate game clock that accumulates time and runs ticks when enough has passed
+///
+///Time is measured in milliseconds, like `WindowBuilder::with_update_rate`. If the timer falls
+///more than a few ticks behind, it only catches up to a limit and drops the rest of the time,
+///so a long hitch can't snowball into ever longer frames.
+pub struct Timer<C: Clock = SystemClock> {
+    clock: C,
+    tick_rate: f64,
+    max_ticks: u32,
+    time_scale: f64,
+    previous: f64,
+    accumulator: f64,
+    elapsed: f64,
+    ticks: u64,
+    paused: bool
 }
 
 impl Timer {
-    ///Create a timer that will tick at the first opportunity
-    pub fn new() -> Timer {
+    ///Create a timer that runs a tick every `tick_rate` milliseconds
+    ///
+    ///The first tick is due at the first opportunity. The tick rate must be positive.
+    pub fn new(tick_rate: f64) -> Timer {
+        Timer::with_clock(tick_rate, SystemClock)
+    }
+}
+
+impl<C: Clock> Timer<C> {
+    ///Create a timer that measures time with the given clock
+    pub fn with_clock(tick_rate: f64, clock: C) -> Timer<C> {
+        check_tick_rate(tick_rate);
+        let previous = clock.now();
         Timer {
-            previous_tick: Instant::now(),
-            wait_time: Duration::from_millis(0)
+            clock,
+            tick_rate,
+            max_ticks: 10,
+            time_scale: 1.0,
+            previous,
+            accumulator: tick_rate,
+            elapsed: 0.0,
+            ticks: 0,
+            paused: false
+        }
+    }
+
+    ///Set the most ticks that can run in one call to `advance` or `tick` (defaults to 10)
+    pub fn with_max_ticks(self, max_ticks: u32) -> Timer<C> {
+        Timer { max_ticks, ..self }
+    }
+
+    ///Set the speed that time passes at, where 1 is real time (defaults to 1)
+    ///
+    ///The time scale can't be negative, but 0 stops time like pausing does
+    pub fn with_time_scale(self, time_scale: f64) -> Timer<C> {
+        check_time_scale(time_scale);
+        Timer { time_scale, ..self }
+    }
+
+    ///Measure the time since the last advance and find how many ticks are now due
+    ///
+    ///The ticks are counted as having run, so the caller should run each of them. Any time
+    ///beyond what `max_ticks` ticks use up is dropped.
+    pub fn advance(&mut self) -> u32 {
+        let now = self.clock.now();
+        if !self.paused {
+            let delta = (now - self.previous).max(0.0) * self.time_scale;
+            self.accumulator += delta;
+            self.elapsed += delta;
+        }
+        self.previous = now;
+        let mut due = 0;
+        while self.accumulator >= self.tick_rate && due < self.max_ticks {
+            self.accumulator -= self.tick_rate;
+            due += 1;
+        }
+        if self.accumulator >= self.tick_rate {
+            self.accumulator %= self.tick_rate;
+        }
+        self.ticks += due as u64;
+        due
+    }
+
+    ///Run the action once for every tick that is due, returning the number of ticks
+    pub fn tick<F>(&mut self, mut action: F) -> u32 where F: FnMut() {
+        let due = self.advance();
+        for _ in 0..due {
+            action();
         }
+        due
+    }
+
+    ///Stop time from passing until the timer is resumed
+    pub fn pause(&mut self) {
+        self.paused = true;
     }
 
-    ///For as many times as appopriate, update
-    pub fn tick<F>(&mut self, mut action: F) where F: FnMut() -> Duration {
-        if self.wait_time.subsec_nanos() == 0 {
-            self.wait_time = action();
-            self.previous_tick = Instant::now();
-        } else {
-            let iterations = self.previous_tick.elapsed().subsec_nanos() / self.wait_time.subsec_nanos();
-            for _ in 0..iterations {
-                self.wait_time = action();
-                self.previous_tick = Instant::now();
-            }
+    ///Let time pass again, not counting the time spent paused
+    pub fn resume(&mut self) {
+        if self.paused {
+            self.paused = false;
+            self.previous = self.clock.now();
         }
     }
+
+    ///Check if the timer is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    ///Get the speed that time passes at, where 1 is real time
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    ///Set the speed that time passes at, for example 0.5 for half speed slow motion
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        check_time_scale(time_scale);
+        self.time_scale = time_scale;
+    }
+
+    ///Get the number of milliseconds between ticks
+    pub fn tick_rate(&self) -> f64 {
+        self.tick_rate
+    }
+
+    ///Set the number of milliseconds between ticks, which must be positive
+    pub fn set_tick_rate(&mut self, tick_rate: f64) {
+        check_tick_rate(tick_rate);
+        self.tick_rate = tick_rate;
+    }
+
+    ///Get the most ticks that can run in one call to `advance` or `tick`
+    pub fn max_ticks(&self) -> u32 {
+        self.max_ticks
+    }
+
+    ///Set the most ticks that can run in one call to `advance` or `tick`
+    pub fn set_max_ticks(&mut self, max_ticks: u32) {
+        self.max_ticks = max_ticks;
+    }
+
+    ///The number of milliseconds that have passed on the timer, scaled and not counting pauses
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    ///The number of ticks that have run
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    ///How far the timer is between the last tick and the next one, from 0 to 1
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.tick_rate) as f32
+    }
+}
+
+// A tick rate of 0 would run every tick that is allowed on each advance and then leave the
+// accumulator as NaN, so the timer would never tick again
+pub(crate) fn check_tick_rate(tick_rate: f64) {
+    assert!(tick_rate > 0.0, "The number of milliseconds between ticks must be positive, not {}", tick_rate);
+}
+
+fn check_time_scale(time_scale: f64) {
+    assert!(time_scale >= 0.0, "The time scale can't be negative, but it was {}", time_scale);
+}
+
+// The number of milliseconds since some arbitrary fixed point, used to drive the game loop
+#[cfg(not(target_arch="wasm32"))]
+pub(crate) fn current_time() -> f64 {
+    thread_local! {
+        static START: Instant = Instant::now();
+    }
+    START.with(|start| {
+        let elapsed = start.elapsed();
+        elapsed.as_secs() as f64 * 1000.0 + elapsed.subsec_nanos() as f64 / 1_000_000.0
+    })
+}
+
+#[cfg(target_arch="wasm32")]
+pub(crate) fn current_time() -> f64 {
+    use ffi::wasm;
+    unsafe { wasm::current_time() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[derive(Clone)]
+    struct MockClock(Rc<Cell<f64>>);
+
+    impl MockClock {
+        fn advance(&self, time: f64) {
+            self.0.set(self.0.get() + time);
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> f64 {
+            self.0.get()
+        }
+    }
+
+    fn timer() -> (Timer<MockClock>, MockClock) {
+        let clock = MockClock(Rc::new(Cell::new(0.0)));
+        (Timer::with_clock(10.0, clock.clone()), clock)
+    }
+
+    #[test]
+    fn first_tick() {
+        let (mut timer, _) = timer();
+        assert_eq!(timer.advance(), 1);
+        assert_eq!(timer.advance(), 0);
+    }
+
+    #[test]
+    fn accumulate() {
+        let (mut timer, clock) = timer();
+        timer.advance();
+        clock.advance(6.0);
+        assert_eq!(timer.advance(), 0);
+        clock.advance(6.0);
+        assert_eq!(timer.advance(), 1);
+        assert_eq!(timer.alpha(), 0.2);
+        clock.advance(2500.0);
+        let mut runs = 0;
+        assert_eq!(timer.tick(|| runs += 1), 10);
+        assert_eq!(runs, 10);
+        assert_eq!(timer.ticks(), 12);
+        assert_eq!(timer.elapsed(), 2512.0);
+    }
+
+    #[test]
+    fn whole_seconds() {
+        let (mut timer, clock) = timer();
+        timer.advance();
+        clock.advance(1005.0);
+        assert_eq!(timer.with_max_ticks(1000).advance(), 100);
+    }
+
+    #[test]
+    fn drop_extra_time() {
+        let (timer, clock) = timer();
+        let mut timer = timer.with_max_ticks(3);
+        timer.advance();
+        clock.advance(95.0);
+        assert_eq!(timer.advance(), 3);
+        assert!(timer.alpha() < 1.0);
+        clock.advance(5.0);
+        assert_eq!(timer.advance(), 1);
+    }
+
+    #[test]
+    fn pausing() {
+        let (mut timer, clock) = timer();
+        timer.advance();
+        timer.pause();
+        clock.advance(100.0);
+        assert_eq!(timer.advance(), 0);
+        clock.advance(100.0);
+        timer.resume();
+        assert_eq!(timer.advance(), 0);
+        clock.advance(10.0);
+        assert_eq!(timer.advance(), 1);
+        assert_eq!(timer.elapsed(), 10.0);
+    }
+
+    #[test]
+    fn time_scale() {
+        let (mut timer, clock) = timer();
+        timer.advance();
+        timer.set_time_scale(0.5);
+        clock.advance(10.0);
+        assert_eq!(timer.advance(), 0);
+        clock.advance(10.0);
+        assert_eq!(timer.advance(), 1);
+        assert_eq!(timer.elapsed(), 10.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_tick_rate() {
+        let (mut timer, _) = timer();
+        timer.set_tick_rate(0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn negative_time_scale() {
+        let (timer, _) = timer();
+        timer.with_time_scale(-1.0);
+    }
 }
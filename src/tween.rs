@@ -0,0 +1,523 @@
+//! A module for smoothly changing values over time
+//!
+//! A Tween moves a value from a start to an end over some duration, following an Easing curve.
+//! Durations have no fixed unit: advance a Tween by one every update with `tick` to measure
+//! them in ticks, or by the milliseconds that have passed with `advance` to measure them in time.
+//! Tweens can be delayed, repeated, and played back and forth, and a Sequence plays several
+//! Tweens one after another.
+
+use geom::{Rectangle, Transform, Vector, lerp};
+use graphics::Color;
+use std::f32::consts::PI;
+
+/// A curve that maps the fraction of a tween that has passed onto how far the value has moved
+///
+/// The `In` curves start slowly, the `Out` curves end slowly, and the `InOut` curves do both.
+/// Back and Elastic curves overshoot the start or end before settling.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[allow(missing_docs)]
+pub enum Easing {
+    Linear,
+    QuadIn, QuadOut, QuadInOut,
+    CubicIn, CubicOut, CubicInOut,
+    QuartIn, QuartOut, QuartInOut,
+    QuintIn, QuintOut, QuintInOut,
+    SineIn, SineOut, SineInOut,
+    ExpoIn, ExpoOut, ExpoInOut,
+    CircIn, CircOut, CircInOut,
+    BackIn, BackOut, BackInOut,
+    ElasticIn, ElasticOut, ElasticInOut,
+    BounceIn, BounceOut, BounceInOut
+}
+
+impl Easing {
+    /// Apply the curve to a fraction from 0 to 1
+    ///
+    /// Every curve maps 0 to 0 and 1 to 1.
+    pub fn apply(self, t: f32) -> f32 {
+        use self::Easing::*;
+        let t = t.max(0.0).min(1.0);
+        match self {
+            Linear => t,
+            QuadIn => quad(t), QuadOut => ease_out(quad, t), QuadInOut => ease_in_out(quad, t),
+            CubicIn => cubic(t), CubicOut => ease_out(cubic, t), CubicInOut => ease_in_out(cubic, t),
+            QuartIn => quart(t), QuartOut => ease_out(quart, t), QuartInOut => ease_in_out(quart, t),
+            QuintIn => quint(t), QuintOut => ease_out(quint, t), QuintInOut => ease_in_out(quint, t),
+            SineIn => sine(t), SineOut => ease_out(sine, t), SineInOut => ease_in_out(sine, t),
+            ExpoIn => expo(t), ExpoOut => ease_out(expo, t), ExpoInOut => ease_in_out(expo, t),
+            CircIn => circ(t), CircOut => ease_out(circ, t), CircInOut => ease_in_out(circ, t),
+            BackIn => back(t), BackOut => ease_out(back, t), BackInOut => ease_in_out(back, t),
+            ElasticIn => elastic(t), ElasticOut => ease_out(elastic, t), ElasticInOut => ease_in_out(elastic, t),
+            BounceIn => bounce(t), BounceOut => ease_out(bounce, t), BounceInOut => ease_in_out(bounce, t)
+        }
+    }
+}
+
+impl Default for Easing {
+    fn default() -> Easing {
+        Easing::Linear
+    }
+}
+
+// Each curve is defined as its In form, and the Out and InOut forms are derived from it
+
+fn ease_out(curve: fn(f32) -> f32, t: f32) -> f32 {
+    1.0 - curve(1.0 - t)
+}
+
+fn ease_in_out(curve: fn(f32) -> f32, t: f32) -> f32 {
+    if t < 0.5 {
+        curve(2.0 * t) / 2.0
+    } else {
+        1.0 - curve(2.0 - 2.0 * t) / 2.0
+    }
+}
+
+fn quad(t: f32) -> f32 { t * t }
+
+fn cubic(t: f32) -> f32 { t * t * t }
+
+fn quart(t: f32) -> f32 { t * t * t * t }
+
+fn quint(t: f32) -> f32 { t * t * t * t * t }
+
+fn sine(t: f32) -> f32 { 1.0 - (t * PI / 2.0).cos() }
+
+fn expo(t: f32) -> f32 {
+    if t == 0.0 { 0.0 } else { 2f32.powf(10.0 * (t - 1.0)) }
+}
+
+fn circ(t: f32) -> f32 { 1.0 - (1.0 - t * t).sqrt() }
+
+fn back(t: f32) -> f32 {
+    const OVERSHOOT: f32 = 1.70158;
+    t * t * ((OVERSHOOT + 1.0) * t - OVERSHOOT)
+}
+
+fn elastic(t: f32) -> f32 {
+    const PERIOD: f32 = 0.3;
+    if t == 0.0 || t == 1.0 {
+        t
+    } else {
+        -2f32.powf(10.0 * (t - 1.0)) * ((t - 1.0 - PERIOD / 4.0) * 2.0 * PI / PERIOD).sin()
+    }
+}
+
+fn bounce(t: f32) -> f32 {
+    let t = 1.0 - t;
+    let out = if t < 1.0 / 2.75 {
+        7.5625 * t * t
+    } else if t < 2.0 / 2.75 {
+        let t = t - 1.5 / 2.75;
+        7.5625 * t * t + 0.75
+    } else if t < 2.5 / 2.75 {
+        let t = t - 2.25 / 2.75;
+        7.5625 * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / 2.75;
+        7.5625 * t * t + 0.984375
+    };
+    1.0 - out
+}
+
+/// A value that can be smoothly interpolated towards another of its kind
+pub trait Tweenable: Copy {
+    /// Find the value a fraction of the way to the target
+    ///
+    /// The fraction may go outside of 0 to 1 for curves that overshoot
+    fn tween(self, target: Self, fraction: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn tween(self, target: f32, fraction: f32) -> f32 {
+        lerp(self, target, fraction)
+    }
+}
+
+impl Tweenable for Vector {
+    fn tween(self, target: Vector, fraction: f32) -> Vector {
+        self + (target - self) * fraction
+    }
+}
+
+impl Tweenable for Color {
+    fn tween(self, target: Color, fraction: f32) -> Color {
+        Color {
+            r: lerp(self.r, target.r, fraction),
+            g: lerp(self.g, target.g, fraction),
+            b: lerp(self.b, target.b, fraction),
+            a: lerp(self.a, target.a, fraction)
+        }
+    }
+}
+
+impl Tweenable for Rectangle {
+    fn tween(self, target: Rectangle, fraction: f32) -> Rectangle {
+        Rectangle::newv(self.top_left().tween(target.top_left(), fraction), self.size().tween(target.size(), fraction))
+    }
+}
+
+/// Transforms are split into a translation, a rotation, a scale and a shear, which are tweened
+/// separately so that a rotating transform keeps its size along the way
+///
+/// Rotations take the shorter way around.
+impl Tweenable for Transform {
+    fn tween(self, target: Transform, fraction: f32) -> Transform {
+        let (translation, angle, scale, shear) = decompose(self);
+        let (target_translation, target_angle, target_scale, target_shear) = decompose(target);
+        let mut turn = (target_angle - angle) % (2.0 * PI);
+        if turn > PI {
+            turn -= 2.0 * PI;
+        } else if turn < -PI {
+            turn += 2.0 * PI;
+        }
+        let scale = scale.tween(target_scale, fraction);
+        let shear = lerp(shear, target_shear, fraction);
+        Transform::translate(translation.tween(target_translation, fraction))
+            * Transform::rotate((angle + turn * fraction) * 180.0 / PI)
+            * Transform([[scale.x, shear, 0.0], [0.0, scale.y, 0.0], [0.0, 0.0, 1.0]])
+    }
+}
+
+// Split a transform into its translation, its rotation in radians, its scale and its shear, where
+// the transform is the translation times the rotation times an upper triangular matrix of the rest
+fn decompose(transform: Transform) -> (Vector, f32, Vector, f32) {
+    let [[a, b, x], [c, d, y], _] = transform.0;
+    let angle = c.atan2(a);
+    let (sin, cos) = angle.sin_cos();
+    let scale = Vector::new((a * a + c * c).sqrt(), cos * d - sin * b);
+    (Vector::new(x, y), angle, scale, cos * b + sin * d)
+}
+
+/// A value moving from a start to an end over a duration
+pub struct Tween<T: Tweenable> {
+    from: T,
+    to: T,
+    duration: f32,
+    easing: Easing,
+    delay: f32,
+    repeats: Option<u32>,
+    yoyo: bool,
+    on_complete: Option<Box<FnMut()>>,
+    time: f32,
+    delay_left: f32,
+    repeats_left: Option<u32>,
+    forward: bool,
+    finished: bool
+}
+
+impl<T: Tweenable> Tween<T> {
+    /// Create a linear tween between two values that lasts for the given duration
+    pub fn new(from: T, to: T, duration: f32) -> Tween<T> {
+        Tween {
+            from,
+            to,
+            duration,
+            easing: Easing::Linear,
+            delay: 0.0,
+            repeats: Some(0),
+            yoyo: false,
+            on_complete: None,
+            time: 0.0,
+            delay_left: 0.0,
+            repeats_left: Some(0),
+            forward: true,
+            finished: false
+        }
+    }
+
+    /// Set the curve the tween follows (defaults to `Easing::Linear`)
+    pub fn with_easing(self, easing: Easing) -> Tween<T> {
+        Tween { easing, ..self }
+    }
+
+    /// Set how long the tween waits before it starts moving (defaults to 0)
+    pub fn with_delay(self, delay: f32) -> Tween<T> {
+        Tween { delay, delay_left: delay, ..self }
+    }
+
+    /// Set how many times the tween plays again after the first time (defaults to 0)
+    pub fn with_repeats(self, repeats: u32) -> Tween<T> {
+        Tween { repeats: Some(repeats), repeats_left: Some(repeats), ..self }
+    }
+
+    /// Make the tween repeat until it is dropped
+    pub fn repeat_forever(self) -> Tween<T> {
+        Tween { repeats: None, repeats_left: None, ..self }
+    }
+
+    /// Set if each repeat plays in the opposite direction of the one before (defaults to false)
+    pub fn with_yoyo(self, yoyo: bool) -> Tween<T> {
+        Tween { yoyo, ..self }
+    }
+
+    /// Set a function to call when the last repeat of the tween finishes
+    pub fn with_on_complete<F>(self, on_complete: F) -> Tween<T> where F: 'static + FnMut() {
+        Tween { on_complete: Some(Box::new(on_complete)), ..self }
+    }
+
+    /// Move the tween forward by one, returning the new value
+    pub fn tick(&mut self) -> T {
+        self.advance(1.0)
+    }
+
+    /// Move the tween forward by an amount of time, returning the new value
+    pub fn advance(&mut self, amount: f32) -> T {
+        self.step(amount);
+        self.value()
+    }
+
+    // Move the tween forward, returning how much of the amount was left over after it finished
+    fn step(&mut self, mut amount: f32) -> f32 {
+        if self.finished {
+            return amount;
+        }
+        let waited = amount.min(self.delay_left);
+        self.delay_left -= waited;
+        amount -= waited;
+        while amount > 0.0 || (self.delay_left <= 0.0 && self.duration <= 0.0) {
+            let remaining = self.duration - self.time;
+            if amount < remaining {
+                self.time += amount;
+                return 0.0;
+            }
+            amount -= remaining.max(0.0);
+            self.time = self.duration;
+            match self.repeats_left {
+                Some(0) => {
+                    self.finished = true;
+                    if let Some(ref mut on_complete) = self.on_complete {
+                        on_complete();
+                    }
+                    return amount;
+                }
+                // A tween that takes no time can't repeat forever, or it would never stop
+                None if self.duration <= 0.0 => {
+                    self.finished = true;
+                    return amount;
+                }
+                Some(repeats) => self.repeats_left = Some(repeats - 1),
+                None => ()
+            }
+            self.time = 0.0;
+            if self.yoyo {
+                self.forward = !self.forward;
+            }
+        }
+        0.0
+    }
+
+    /// Get the current value of the tween
+    pub fn value(&self) -> T {
+        let fraction = if self.duration <= 0.0 { 1.0 } else { self.time / self.duration };
+        let fraction = if self.forward { fraction } else { 1.0 - fraction };
+        self.from.tween(self.to, self.easing.apply(fraction))
+    }
+
+    /// Check if the tween has played all of its repeats
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Start the tween over from the beginning, including its delay
+    pub fn reset(&mut self) {
+        self.time = 0.0;
+        self.delay_left = self.delay;
+        self.repeats_left = self.repeats;
+        self.forward = true;
+        self.finished = false;
+    }
+}
+
+/// A series of tweens that play one after another
+///
+/// Any time left over when one tween finishes carries over into the next one.
+pub struct Sequence<T: Tweenable> {
+    tweens: Vec<Tween<T>>,
+    current: usize
+}
+
+impl<T: Tweenable> Sequence<T> {
+    /// Create an empty sequence
+    pub fn new() -> Sequence<T> {
+        Sequence {
+            tweens: Vec::new(),
+            current: 0
+        }
+    }
+
+    /// Create a copy of the sequence with a tween added to the end
+    pub fn then(mut self, tween: Tween<T>) -> Sequence<T> {
+        self.tweens.push(tween);
+        self
+    }
+
+    /// Move the sequence forward by one, returning the new value
+    ///
+    /// Panics if the sequence is empty
+    pub fn tick(&mut self) -> T {
+        self.advance(1.0)
+    }
+
+    /// Move the sequence forward by an amount of time, returning the new value
+    ///
+    /// Panics if the sequence is empty
+    pub fn advance(&mut self, mut amount: f32) -> T {
+        while self.current < self.tweens.len() {
+            amount = self.tweens[self.current].step(amount);
+            if !self.tweens[self.current].is_finished() || self.current + 1 == self.tweens.len() {
+                break;
+            }
+            self.current += 1;
+        }
+        self.value()
+    }
+
+    /// Get the current value of the sequence
+    ///
+    /// Panics if the sequence is empty
+    pub fn value(&self) -> T {
+        self.tweens[self.current].value()
+    }
+
+    /// Check if every tween in the sequence has finished
+    pub fn is_finished(&self) -> bool {
+        self.tweens.last().map(Tween::is_finished).unwrap_or(true)
+    }
+
+    /// Start the sequence over from the first tween
+    pub fn reset(&mut self) {
+        for tween in self.tweens.iter_mut() {
+            tween.reset();
+        }
+        self.current = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geom::about_equal;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    const EASINGS: &[Easing] = &[
+        Easing::Linear,
+        Easing::QuadIn, Easing::QuadOut, Easing::QuadInOut,
+        Easing::CubicIn, Easing::CubicOut, Easing::CubicInOut,
+        Easing::QuartIn, Easing::QuartOut, Easing::QuartInOut,
+        Easing::QuintIn, Easing::QuintOut, Easing::QuintInOut,
+        Easing::SineIn, Easing::SineOut, Easing::SineInOut,
+        Easing::ExpoIn, Easing::ExpoOut, Easing::ExpoInOut,
+        Easing::CircIn, Easing::CircOut, Easing::CircInOut,
+        Easing::BackIn, Easing::BackOut, Easing::BackInOut,
+        Easing::ElasticIn, Easing::ElasticOut, Easing::ElasticInOut,
+        Easing::BounceIn, Easing::BounceOut, Easing::BounceInOut
+    ];
+
+    #[test]
+    fn endpoints() {
+        for easing in EASINGS {
+            assert!(about_equal(easing.apply(0.0), 0.0), "{:?}", easing);
+            assert!(about_equal(easing.apply(1.0), 1.0), "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn curves() {
+        assert!(about_equal(Easing::QuadIn.apply(0.5), 0.25));
+        assert!(about_equal(Easing::QuadOut.apply(0.5), 0.75));
+        assert!(about_equal(Easing::CubicInOut.apply(0.5), 0.5));
+        assert!(Easing::BackIn.apply(0.2) < 0.0);
+        assert!(Easing::ElasticOut.apply(0.2) > 1.0);
+        assert!(about_equal(Easing::BounceOut.apply(1.0 / 2.75), 1.0));
+    }
+
+    #[test]
+    fn tweenables() {
+        assert_eq!(Vector::new(0, 10).tween(Vector::new(10, 0), 0.5), Vector::new(5, 5));
+        assert_eq!(Color::black().tween(Color::white(), 0.5), Color { r: 0.5, g: 0.5, b: 0.5, a: 1.0 });
+        assert_eq!(Rectangle::new(0, 0, 10, 10).tween(Rectangle::new(10, 10, 20, 30), 0.5), Rectangle::new(5, 5, 15, 20));
+        let halfway = Transform::identity().tween(Transform::translate(Vector::new(10, 0)), 0.5);
+        assert_eq!(halfway * Vector::zero(), Vector::new(5, 0));
+    }
+
+    #[test]
+    fn transforms() {
+        // A half turn keeps its size halfway through, instead of collapsing to nothing
+        let halfway = Transform::rotate(0).tween(Transform::rotate(180), 0.5);
+        assert!(about_equal((halfway * Vector::new(2, 0)).len(), 2.0));
+        let eighth = Transform::rotate(0).tween(Transform::rotate(90), 0.5);
+        assert_eq!(eighth * Vector::new(1, 0), Transform::rotate(45) * Vector::new(1, 0));
+        // Turning from 350 to 10 degrees goes through 0 rather than 180
+        let wrapped = Transform::rotate(350).tween(Transform::rotate(10), 0.5);
+        assert_eq!(wrapped * Vector::new(1, 0), Vector::new(1, 0));
+        let from = Transform::translate(Vector::new(10, 0)) * Transform::rotate(90) * Transform::scale(Vector::new(2, 1));
+        let to = Transform::translate(Vector::new(20, 10)) * Transform::rotate(90) * Transform::scale(Vector::new(4, -1));
+        let between = from.tween(to, 0.5);
+        assert_eq!(between * Vector::zero(), Vector::new(15, 5));
+        assert_eq!(between * Vector::new(1, 0), Vector::new(15, 8));
+        assert_eq!(between * Vector::new(0, 1), Vector::new(15, 5));
+        assert_eq!(from.tween(to, 1.0) * Vector::new(1, 1), to * Vector::new(1, 1));
+    }
+
+    #[test]
+    fn ticks() {
+        let mut tween = Tween::new(0.0, 10.0, 4.0);
+        assert_eq!(tween.value(), 0.0);
+        assert_eq!(tween.tick(), 2.5);
+        assert_eq!(tween.advance(2.0), 7.5);
+        assert!(!tween.is_finished());
+        assert_eq!(tween.advance(5.0), 10.0);
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn delay() {
+        let mut tween = Tween::new(0.0, 10.0, 10.0).with_delay(5.0);
+        assert_eq!(tween.advance(5.0), 0.0);
+        assert_eq!(tween.advance(5.0), 5.0);
+        tween.reset();
+        assert_eq!(tween.advance(7.0), 2.0);
+    }
+
+    #[test]
+    fn repeat_and_yoyo() {
+        let mut tween = Tween::new(0.0, 10.0, 10.0).with_repeats(2).with_yoyo(true);
+        assert_eq!(tween.advance(12.0), 8.0);
+        assert_eq!(tween.advance(10.0), 2.0);
+        assert!(!tween.is_finished());
+        assert_eq!(tween.advance(100.0), 10.0);
+        assert!(tween.is_finished());
+        let mut forever = Tween::new(0.0, 10.0, 10.0).repeat_forever();
+        assert_eq!(forever.advance(1005.0), 5.0);
+        assert!(!forever.is_finished());
+    }
+
+    #[test]
+    fn on_complete() {
+        let count = Rc::new(Cell::new(0));
+        let counter = count.clone();
+        let mut tween = Tween::new(0.0, 1.0, 2.0)
+            .with_repeats(1)
+            .with_on_complete(move || counter.set(counter.get() + 1));
+        tween.advance(3.0);
+        assert_eq!(count.get(), 0);
+        tween.advance(3.0);
+        tween.advance(3.0);
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn sequence() {
+        let mut sequence = Sequence::new()
+            .then(Tween::new(Vector::zero(), Vector::new(10, 0), 10.0))
+            .then(Tween::new(Vector::new(10, 0), Vector::new(10, 10), 10.0).with_delay(5.0));
+        assert_eq!(sequence.advance(5.0), Vector::new(5, 0));
+        assert_eq!(sequence.advance(10.0), Vector::new(10, 0));
+        assert_eq!(sequence.advance(5.0), Vector::new(10, 5));
+        assert!(!sequence.is_finished());
+        assert_eq!(sequence.advance(50.0), Vector::new(10, 10));
+        assert!(sequence.is_finished());
+    }
+}